@@ -0,0 +1,464 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+
+use similar::TextDiff;
+
+/// Guesses the load format of a local configuration, either `set` or `text`.
+pub fn config_format(cfg: &str) -> &'static str {
+    let mut statements = cfg
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    if statements.peek().is_some()
+        && statements.all(|line| line.starts_with("set ") || line.starts_with("delete "))
+    {
+        "set"
+    } else {
+        "text"
+    }
+}
+
+/// Load action for a configuration in the given `config_format`, as set
+/// commands can't be merged with `update`.
+pub fn load_action(format: &str) -> &'static str {
+    match format {
+        "set" => "set",
+        _ => "update",
+    }
+}
+
+/// Removes what Junos adds to the configuration it returns, so it can be
+/// compared with a local file: the `## Last commit` style comments, the
+/// `version` statement, `## SECRET-DATA` markers and `/* */` annotations.
+fn normalize_config(cfg: &str) -> String {
+    let mut output = String::with_capacity(cfg.len());
+    let mut in_annotation = false;
+    for line in cfg.lines() {
+        let statement = line.trim();
+        if in_annotation || statement.starts_with("/*") {
+            in_annotation = !statement.ends_with("*/");
+            continue;
+        }
+        if line.starts_with("## Last ")
+            || (line.starts_with("version ") && line.ends_with(';'))
+            || line.starts_with("set version ")
+        {
+            continue;
+        }
+        output.push_str(line.strip_suffix(" ## SECRET-DATA").unwrap_or(line));
+        output.push('\n');
+    }
+    output
+}
+
+/// Compares the running configuration with a local one in the given
+/// `config_format` as unified diff.
+///
+/// Only the statements are compared: differences in indentation, whitespace
+/// and the order of statements within a hierarchy level are ignored.
+pub fn config_diff(running: &str, local: &str, format: &str, old_name: &str, new_name: &str) -> String {
+    let (running, local) = (normalize_config(running), normalize_config(local));
+    let (old, new) = match format {
+        "set" => {
+            let old: Vec<_> = running.lines().map(Statement::leaf).filter(|s| !s.text.is_empty()).collect();
+            let new: Vec<_> = local.lines().map(Statement::leaf).filter(|s| !s.text.is_empty()).collect();
+            let new = follow_order(&old, new);
+            (render_set(&old), render_set(&new))
+        }
+        _ => {
+            let old = parse_statements(&running);
+            let new = follow_order(&old, parse_statements(&local));
+            (render_text(&old, 0), render_text(&new, 0))
+        }
+    };
+    unified_diff(&old, &new, old_name, new_name)
+}
+
+/// Statement of a configuration, with the statements of its hierarchy level
+/// if it opens one.
+#[derive(Debug, PartialEq)]
+struct Statement {
+    /// Statement with whitespace collapsed, without `;` or `{`
+    text: String,
+    children: Option<Vec<Statement>>,
+}
+
+impl Statement {
+    fn leaf(text: &str) -> Statement {
+        Statement {
+            text: collapse_whitespace(text),
+            children: None,
+        }
+    }
+}
+
+/// Collapses runs of whitespace outside of quotes into single spaces.
+fn collapse_whitespace(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut space = false;
+    for c in text.trim().chars() {
+        if c.is_whitespace() && !quoted {
+            space = true;
+            continue;
+        }
+        if space {
+            output.push(' ');
+            space = false;
+        }
+        if c == '"' {
+            quoted = !quoted;
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Parses a curly-brace configuration. Unbalanced braces are tolerated, so a
+/// broken local file still shows up in the diff.
+fn parse_statements(cfg: &str) -> Vec<Statement> {
+    let mut levels: Vec<(String, Vec<Statement>)> = vec![(String::new(), vec![])];
+    let mut text = String::new();
+    let mut quoted = false;
+    let mut comment = false;
+    for c in cfg.chars() {
+        match c {
+            '\n' if comment => comment = false,
+            _ if comment => {}
+            '"' => {
+                quoted = !quoted;
+                text.push(c);
+            }
+            _ if quoted => text.push(c),
+            '#' if text.trim().is_empty() => comment = true,
+            ';' => {
+                let statement = Statement::leaf(&text);
+                text.clear();
+                levels.last_mut().unwrap().1.push(statement);
+            }
+            '{' => {
+                levels.push((collapse_whitespace(&text), vec![]));
+                text.clear();
+            }
+            '}' => {
+                if !text.trim().is_empty() {
+                    let statement = Statement::leaf(&text);
+                    levels.last_mut().unwrap().1.push(statement);
+                }
+                text.clear();
+                if levels.len() > 1 {
+                    let (header, children) = levels.pop().unwrap();
+                    levels.last_mut().unwrap().1.push(Statement {
+                        text: header,
+                        children: Some(children),
+                    });
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.trim().is_empty() {
+        levels.last_mut().unwrap().1.push(Statement::leaf(&text));
+    }
+    while levels.len() > 1 {
+        let (header, children) = levels.pop().unwrap();
+        levels.last_mut().unwrap().1.push(Statement {
+            text: header,
+            children: Some(children),
+        });
+    }
+    levels.pop().unwrap().1
+}
+
+/// Orders the statements of `new` like the matching ones of `old` on every
+/// hierarchy level. Statements only in `new` follow in their original order.
+fn follow_order(old: &[Statement], new: Vec<Statement>) -> Vec<Statement> {
+    let mut positions: HashMap<(String, bool), VecDeque<usize>> = HashMap::new();
+    for (idx, statement) in new.iter().enumerate() {
+        let key = (statement.text.clone(), statement.children.is_some());
+        positions.entry(key).or_default().push_back(idx);
+    }
+
+    let mut new: Vec<Option<Statement>> = new.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(new.len());
+    for old_statement in old {
+        let key = (old_statement.text.clone(), old_statement.children.is_some());
+        let Some(idx) = positions.get_mut(&key).and_then(VecDeque::pop_front) else {
+            continue;
+        };
+        let mut statement = new[idx].take().unwrap();
+        if let (Some(old_children), Some(children)) = (&old_statement.children, statement.children.take()) {
+            statement.children = Some(follow_order(old_children, children));
+        }
+        ordered.push(statement);
+    }
+    ordered.extend(new.into_iter().flatten());
+    ordered
+}
+
+/// Renders statements as curly-brace configuration indented like Junos does.
+fn render_text(statements: &[Statement], depth: usize) -> String {
+    let mut output = String::new();
+    let indent = "    ".repeat(depth);
+    for statement in statements {
+        match &statement.children {
+            Some(children) => {
+                _ = writeln!(output, "{}{} {{", indent, statement.text);
+                output += &render_text(children, depth + 1);
+                _ = writeln!(output, "{}}}", indent);
+            }
+            None => _ = writeln!(output, "{}{};", indent, statement.text),
+        }
+    }
+    output
+}
+
+fn render_set(statements: &[Statement]) -> String {
+    statements.iter().map(|statement| format!("{}\n", statement.text)).collect()
+}
+
+/// Builds a unified diff between two configurations.
+///
+/// For curly-brace configurations every hunk header carries the `[edit ...]`
+/// hierarchy the hunk starts in, similar to the function context of `diff -p`.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines: Vec<&str> = old.lines().collect();
+
+    let mut output = String::new();
+    for (idx, hunk) in diff.unified_diff().context_radius(3).iter_hunks().enumerate() {
+        if idx == 0 {
+            _ = writeln!(output, "--- {}", old_name);
+            _ = writeln!(output, "+++ {}", new_name);
+        }
+
+        let start = hunk
+            .ops()
+            .first()
+            .map(|op| op.old_range().start)
+            .unwrap_or(0);
+        let hierarchy = hierarchy_at(&old_lines[..start]);
+        if hierarchy.is_empty() {
+            _ = writeln!(output, "{}", hunk.header());
+        } else {
+            _ = writeln!(output, "{} [edit {}]", hunk.header(), hierarchy.join(" "));
+        }
+
+        for change in hunk.iter_changes() {
+            _ = write!(output, "{}{}", change.tag(), change.value());
+            if change.missing_newline() {
+                _ = writeln!(output);
+            }
+        }
+    }
+
+    output
+}
+
+/// Returns the hierarchy a curly-brace configuration is in after the given lines.
+fn hierarchy_at(lines: &[&str]) -> Vec<String> {
+    let mut hierarchy = vec![];
+    for line in lines {
+        let line = line.trim();
+        if let Some(statement) = line.strip_suffix('{') {
+            hierarchy.push(statement.trim().to_owned());
+        } else if line.starts_with('}') {
+            hierarchy.pop();
+        }
+    }
+    hierarchy
+}
+
+/// Converts a Junos `compare rollback` diff into a unified diff.
+///
+/// Every `[edit ...]` section becomes one hunk. Junos does not report line
+/// numbers, so the hunk positions are synthesized and only consistent within
+/// the diff itself.
+pub fn junos_to_unified(diff: &str, old_name: &str, new_name: &str) -> String {
+    let mut sections: Vec<(&str, Vec<&str>)> = vec![];
+    for line in diff.lines() {
+        if line.starts_with("[edit") {
+            sections.push((line, vec![]));
+        } else if line.is_empty() {
+            continue;
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line);
+        } else {
+            sections.push(("", vec![line]));
+        }
+    }
+
+    let mut output = String::new();
+    if sections.is_empty() {
+        return output;
+    }
+    _ = writeln!(output, "--- {}", old_name);
+    _ = writeln!(output, "+++ {}", new_name);
+
+    let (mut old_start, mut new_start) = (1, 1);
+    for (section, lines) in sections {
+        let removed = lines.iter().filter(|l| l.starts_with('-')).count();
+        let added = lines.iter().filter(|l| l.starts_with('+')).count();
+        let context = lines.len() - removed - added;
+        let (old_len, new_len) = (context + removed, context + added);
+
+        _ = write!(
+            output,
+            "@@ -{},{} +{},{} @@",
+            old_start, old_len, new_start, new_len
+        );
+        if section.is_empty() {
+            _ = writeln!(output);
+        } else {
+            _ = writeln!(output, " {}", section);
+        }
+        for line in lines {
+            // Junos already indents context lines by one space.
+            if line.starts_with(['-', '+', ' ']) {
+                _ = writeln!(output, "{}", line);
+            } else {
+                _ = writeln!(output, " {}", line);
+            }
+        }
+
+        old_start += old_len;
+        new_start += new_len;
+    }
+
+    output
+}
+
+/// Highlights a Junos or unified diff with ANSI colors.
+pub fn colorize(diff: &str) -> String {
+    let mut output = String::new();
+    for line in diff.lines() {
+        let color = if line.starts_with("+++") || line.starts_with("---") {
+            "1"
+        } else if line.starts_with("@@") || line.starts_with("[edit") {
+            "36"
+        } else if line.starts_with('+') {
+            "32"
+        } else if line.starts_with('-') {
+            "31"
+        } else {
+            _ = writeln!(output, "{}", line);
+            continue;
+        };
+        _ = writeln!(output, "\x1b[{}m{}\x1b[0m", color, line);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_diff, config_format, junos_to_unified, normalize_config};
+
+    #[test]
+    fn normalizes_text_config() {
+        let running = "## Last commit: 2024-05-02 10:00:00 UTC by jdoe
+version 21.4R3.15;
+system {
+    /* managed by rucli */
+    host-name r1;
+    /* spanning
+       lines */
+    root-authentication {
+        encrypted-password \"$6$salt$hash\"; ## SECRET-DATA
+    }
+}
+";
+        assert_eq!(
+            normalize_config(running),
+            "system {
+    host-name r1;
+    root-authentication {
+        encrypted-password \"$6$salt$hash\";
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn normalizes_set_config() {
+        let running = "## Last commit: 2024-05-02 10:00:00 UTC by jdoe
+set version 21.4R3.15
+set system host-name r1
+";
+        assert_eq!(normalize_config(running), "set system host-name r1\n");
+    }
+
+    #[test]
+    fn guesses_format() {
+        assert_eq!(config_format("# comment\nset system host-name r1\ndelete snmp\n"), "set");
+        assert_eq!(config_format("system {\n    host-name r1;\n}\n"), "text");
+        assert_eq!(config_format(""), "text");
+    }
+
+    #[test]
+    fn ignores_layout_and_order() {
+        let running = "system {
+    host-name r1;
+    services {
+        ssh;
+        netconf {
+            ssh;
+        }
+    }
+}
+interfaces {
+    ge-0/0/0 {
+        description \"to  r2\";
+    }
+}
+";
+        let local = "interfaces { ge-0/0/0 { description \"to  r2\"; } }
+system {
+  services {
+    netconf { ssh; }
+    ssh;
+  }
+  host-name   r1;
+}
+";
+        assert_eq!(config_diff(running, local, "text", "committed", "local"), "");
+        assert_eq!(
+            config_diff("set system host-name r1\nset system services ssh\n", "set system services  ssh\nset system host-name r1", "set", "committed", "local"),
+            ""
+        );
+    }
+
+    #[test]
+    fn diffs_statements() {
+        let running = "system {\n    host-name r1;\n    services {\n        ssh;\n    }\n}\n";
+        let local = "system {\n  services {\n    ssh;\n    netconf {\n      ssh;\n    }\n  }\n  host-name r2;\n}\n";
+        assert_eq!(
+            config_diff(running, local, "text", "committed", "local"),
+            "--- committed
++++ local
+@@ -1,6 +1,9 @@
+ system {
+-    host-name r1;
+     services {
+         ssh;
++        netconf {
++            ssh;
++        }
+     }
++    host-name r2;
+ }
+"
+        );
+    }
+
+    #[test]
+    fn converts_junos_diff() {
+        let diff = "\n[edit interfaces ge-0/0/0 unit 0]\n     description uplink;\n-    family inet6;\n+    family inet;\n";
+        assert_eq!(
+            junos_to_unified(diff, "committed", "candidate"),
+            "--- committed\n+++ candidate\n@@ -1,2 +1,2 @@ [edit interfaces ge-0/0/0 unit 0]\n     description uplink;\n-    family inet6;\n+    family inet;\n"
+        );
+    }
+}
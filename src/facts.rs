@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+use quick_xml::de::from_str;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::netconf::xml::{seconds, RPCError};
+use crate::netconf::{NETCONFClient, NETCONFError, RpcOutcome};
+
+/// Inventory facts of a Junos device.
+#[derive(Debug, Default, Serialize)]
+pub struct DeviceFacts {
+    pub hostname: Option<String>,
+    pub model: Option<String>,
+    pub version: Option<String>,
+    pub serial_number: Option<String>,
+    /// Seconds since the system booted
+    pub uptime: Option<u64>,
+    pub route_engines: Vec<RouteEngine>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RouteEngine {
+    pub slot: Option<String>,
+    #[serde(rename(deserialize = "mastership-state"))]
+    pub mastership_state: Option<String>,
+    pub status: Option<String>,
+    pub model: Option<String>,
+    /// Seconds since the routing engine booted
+    #[serde(rename(deserialize = "up-time"), deserialize_with = "seconds")]
+    #[serde(default)]
+    pub uptime: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MultiRoutingEngineResults<T> {
+    #[serde(rename = "multi-routing-engine-item")]
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SoftwareItem {
+    #[serde(rename = "software-information")]
+    software_information: SoftwareInformation,
+}
+
+#[derive(Debug, Deserialize)]
+struct SoftwareInformation {
+    #[serde(rename = "host-name")]
+    host_name: Option<String>,
+    #[serde(rename = "product-model")]
+    product_model: Option<String>,
+    #[serde(rename = "junos-version")]
+    junos_version: Option<String>,
+    #[serde(rename = "package-information", default)]
+    package_information: Vec<PackageInformation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageInformation {
+    comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChassisInventory {
+    chassis: Chassis,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chassis {
+    #[serde(rename = "serial-number")]
+    serial_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RouteEngineInformation {
+    #[serde(rename = "route-engine", default)]
+    route_engines: Vec<RouteEngine>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UptimeItem {
+    #[serde(rename = "system-uptime-information")]
+    system_uptime_information: SystemUptimeInformation,
+}
+
+#[derive(Debug, Deserialize)]
+struct SystemUptimeInformation {
+    #[serde(rename = "system-booted-time")]
+    system_booted_time: Option<BootedTime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BootedTime {
+    #[serde(rename = "time-length", deserialize_with = "seconds", default)]
+    time_length: Option<u64>,
+}
+
+/// Calls an RPC and deserializes its reply, using the first routing engine of
+/// `multi-routing-engine-results`.
+fn call<T: DeserializeOwned, I: DeserializeOwned>(
+    client: &mut NETCONFClient,
+    name: &str,
+    warnings: &mut Vec<RPCError>,
+    item: impl Fn(I) -> T,
+) -> Result<Option<T>, NETCONFError> {
+    let outcome = client.call(name, &[], &[], "xml".to_string())?;
+    warnings.extend(outcome.warnings);
+    if outcome.value.starts_with("<multi-routing-engine-results") {
+        let results: MultiRoutingEngineResults<I> = from_str(&outcome.value)?;
+        return Ok(results.items.into_iter().next().map(item));
+    }
+    Ok(Some(from_str(&outcome.value)?))
+}
+
+/// Gathers the facts of the device the client is connected to.
+pub fn gather(client: &mut NETCONFClient) -> Result<RpcOutcome<DeviceFacts>, NETCONFError> {
+    let mut warnings = vec![];
+    let mut facts = DeviceFacts::default();
+
+    let software: Option<SoftwareInformation> = call(
+        client,
+        "get-software-information",
+        &mut warnings,
+        |item: SoftwareItem| item.software_information,
+    )?;
+    if let Some(software) = software {
+        facts.hostname = software.host_name;
+        facts.model = software.product_model;
+        // Releases before 15.1 only state the version in the package comments,
+        // e.g. "JUNOS Base OS boot [12.3R12.4]".
+        facts.version = software.junos_version.or_else(|| {
+            software.package_information.iter().find_map(|package| {
+                let comment = package.comment.as_ref()?;
+                let start = comment.find('[')? + 1;
+                let end = start + comment[start..].find(']')?;
+                Some(comment[start..end].to_string())
+            })
+        });
+    }
+
+    let inventory: Option<ChassisInventory> =
+        call(client, "get-chassis-inventory", &mut warnings, |item: ChassisInventory| item)?;
+    facts.serial_number = inventory.and_then(|inventory| inventory.chassis.serial_number);
+
+    let route_engines: Option<RouteEngineInformation> = call(
+        client,
+        "get-route-engine-information",
+        &mut warnings,
+        |item: RouteEngineInformation| item,
+    )?;
+    facts.route_engines = route_engines.map(|r| r.route_engines).unwrap_or_default();
+
+    let uptime: Option<SystemUptimeInformation> = call(
+        client,
+        "get-system-uptime-information",
+        &mut warnings,
+        |item: UptimeItem| item.system_uptime_information,
+    )?;
+    facts.uptime = uptime
+        .and_then(|uptime| uptime.system_booted_time)
+        .and_then(|booted| booted.time_length);
+
+    Ok(RpcOutcome {
+        value: facts,
+        warnings,
+    })
+}
+
+/// Formats seconds like `3d 04:05:06`.
+fn format_duration(seconds: u64) -> String {
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    format!("{}d {:02}:{:02}:{:02}", days, rest / 3600, rest % 3600 / 60, rest % 60)
+}
+
+impl Display for DeviceFacts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = || "-".to_string();
+        writeln!(f, "Hostname: {}", self.hostname.clone().unwrap_or_else(unknown))?;
+        writeln!(f, "Model: {}", self.model.clone().unwrap_or_else(unknown))?;
+        writeln!(f, "Version: {}", self.version.clone().unwrap_or_else(unknown))?;
+        writeln!(f, "Serial number: {}", self.serial_number.clone().unwrap_or_else(unknown))?;
+        writeln!(f, "Uptime: {}", self.uptime.map(format_duration).unwrap_or_else(unknown))?;
+        for re in &self.route_engines {
+            writeln!(
+                f,
+                "RE{}: {} {} {} up {}",
+                re.slot.clone().unwrap_or_default(),
+                re.model.clone().unwrap_or_else(unknown),
+                re.mastership_state.clone().unwrap_or_else(unknown),
+                re.status.clone().unwrap_or_else(unknown),
+                re.uptime.map(format_duration).unwrap_or_else(unknown),
+            )?;
+        }
+        Ok(())
+    }
+}
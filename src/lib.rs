@@ -1,2 +1,5 @@
+pub mod diff;
+pub mod facts;
 pub mod netconf;
 pub mod ssh;
+pub mod template;
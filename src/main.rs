@@ -1,13 +1,23 @@
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, IsTerminal, Read, Write};
 use std::path::Path;
-use std::{env, fs};
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
 
-use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 use ssh2_config::{ParseRule, SshConfig};
+use tracing_subscriber::EnvFilter;
 
-use rucli::netconf::NETCONFClient;
-use rucli::ssh::SSHConnection;
+use rucli::diff;
+use rucli::facts;
+use rucli::netconf::xml::{ConfigurationDatabase, RPCError};
+use rucli::netconf::transcript::{RecordingTransport, ReplayTransport};
+use rucli::netconf::{NETCONFClient, RpcOutcome, Transport};
+use rucli::ssh::{SSHConnection, SSHTransport};
+use rucli::template::TemplateContext;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -20,12 +30,76 @@ struct Cli {
     #[arg(long, short, env)]
     password: Option<String>,
 
-    #[arg(long, short, action=ArgAction::SetTrue)]
+    #[arg(long, action=ArgAction::SetTrue)]
     debug: bool,
 
+    /// Logs RPC traffic to stderr; repeat for more detail (-v, -vv, -vvv)
+    #[arg(long, short, action=ArgAction::Count)]
+    verbose: u8,
+
     #[arg(long, short, action=ArgAction::SetTrue)]
     diff: bool,
 
+    /// Rejects replies containing elements unknown to rucli
+    #[arg(long, action=ArgAction::SetTrue)]
+    strict: bool,
+
+    /// Seconds to wait for the TCP connection
+    #[arg(long)]
+    connect_timeout: Option<u64>,
+
+    /// Seconds the SSH handshake, authentication and opening the NETCONF
+    /// subsystem may take, the connect timeout if not given
+    #[arg(long)]
+    handshake_timeout: Option<u64>,
+
+    /// Seconds to wait for the reply to each RPC
+    #[arg(long)]
+    timeout: Option<u64>,
+
+    /// Seconds to wait for further data while receiving a reply
+    #[arg(long)]
+    idle_timeout: Option<u64>,
+
+    /// Interval in seconds to send SSH keepalives in while waiting for replies
+    #[arg(long)]
+    keepalive: Option<u64>,
+
+    /// Writes every NETCONF message exchanged to this file as JSON lines
+    #[arg(long)]
+    record: Option<String>,
+
+    /// Keeps passwords and keys in the file written by --record instead of redacting them
+    #[arg(long, requires = "record")]
+    record_unredacted: bool,
+
+    /// Plays back a file written by --record instead of connecting to the device
+    #[arg(long)]
+    replay: Option<String>,
+
+    /// Configuration database to make changes in
+    #[arg(long, value_enum, default_value_t = Database::Shared)]
+    db: Database,
+
+    /// Discards uncommitted changes found in the candidate before loading instead of refusing to continue
+    #[arg(long, action=ArgAction::SetTrue)]
+    discard_uncommitted: bool,
+
+    /// How long to retry taking the configuration lock while someone else holds it, e.g. `60s` or `5m`
+    #[arg(long, value_parser = parse_duration)]
+    lock_wait: Option<Duration>,
+
+    /// Makes changes in and compares against this instance of the ephemeral configuration database
+    #[arg(long, conflicts_with = "db")]
+    ephemeral_instance: Option<String>,
+
+    #[command(flatten)]
+    diff_output: DiffArgs,
+
+    /// Prints a single JSON document describing the result instead of plain text
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -34,8 +108,119 @@ struct Cli {
 enum Format {
     Text,
     JSON,
-    // TODO: We need to get into quick-xml to find out how to handle internal XML as strings.
-    // XML
+    XML,
+}
+
+impl Format {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::JSON => "json",
+            Format::XML => "xml",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Database {
+    /// Locks the shared candidate configuration
+    Shared,
+    /// Edits a private copy of the candidate, as with `configure private`
+    Private,
+    /// Edits the candidate while keeping others from changing it, as with `configure exclusive`
+    Exclusive,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Table {
+    Interfaces,
+    Bgp,
+    Lldp,
+    Routes,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum OutputFormat {
+    Text,
+    JSON,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum DiffFormat {
+    Junos,
+    Unified,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// Colorizes diffs
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Pipes diffs through $PAGER when stdout is a terminal
+    #[arg(long, action=ArgAction::SetTrue)]
+    pager: bool,
+
+    /// Renders diffs in the Junos `[edit ...]` format or as unified diff
+    #[arg(long, value_enum, default_value_t = DiffFormat::Junos)]
+    diff_format: DiffFormat,
+}
+
+impl DiffArgs {
+    /// Prints a diff according to the selected format, color and pager settings.
+    /// `unified` tells whether the diff is already a unified diff.
+    fn print(&self, diff_reply: &str, unified: bool) {
+        let mut output = if !unified && self.diff_format == DiffFormat::Unified {
+            diff::junos_to_unified(diff_reply, "committed", "candidate")
+        } else {
+            diff_reply.to_owned()
+        };
+
+        let is_terminal = io::stdout().is_terminal();
+        if self.color == ColorChoice::Always || (self.color == ColorChoice::Auto && is_terminal) {
+            output = diff::colorize(&output);
+        }
+
+        if self.pager && is_terminal {
+            let pager = env::var("PAGER").unwrap_or("less -R".to_owned());
+            if let Ok(mut child) = process::Command::new("sh")
+                .arg("-c")
+                .arg(&pager)
+                .stdin(process::Stdio::piped())
+                .spawn()
+            {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // The pager may be quit before reading everything.
+                    let _ = stdin.write_all(output.as_bytes());
+                }
+                let _ = child.wait();
+                return;
+            }
+        }
+
+        print!("{}", output);
+        if !output.ends_with('\n') {
+            println!();
+        }
+    }
+}
+
+#[derive(Args)]
+struct TemplateArgs {
+    /// Renders the configuration as template with variables from this TOML file
+    #[arg(long)]
+    vars: Vec<String>,
+
+    /// Renders the configuration as template with variables from this TOML inventory
+    #[arg(long)]
+    inventory: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -45,17 +230,34 @@ enum Commands {
         #[clap(value_enum)]
         format: Format,
 
+        /// Writes the output to stdout while it is received instead of after the command finished
+        #[arg(long, action=ArgAction::SetTrue)]
+        stream: bool,
+
+        /// Further command to execute, can be given multiple times. Multiple commands are pipelined.
+        #[arg(long = "command", short)]
+        commands: Vec<String>,
+
+        /// File with one command per line to execute pipelined, or `-` to read from stdin
+        #[arg(long)]
+        batch: Option<String>,
+
         command: Vec<String>,
     },
 
     /// Applies local configuration file on router
     Apply {
+        /// Path to the configuration file, or `-` to read from stdin
         local_file: String,
         confirm_timeout: Option<i32>,
+
+        #[command(flatten)]
+        template: TemplateArgs,
     },
 
     /// Incrementally updates the config from the given config statements
     EditConfig {
+        /// Configuration statements separated by `;`, or `-` to read from stdin
         statement: String,
         confirm_timeout: Option<i32>,
     },
@@ -64,14 +266,442 @@ enum Commands {
     Confirm,
 
     /// Loads local configuration onto router and shows a diff
-    Check { local_file: String },
+    Check {
+        /// Path to the configuration file, or `-` to read from stdin
+        local_file: String,
+
+        /// Diffs locally against the committed configuration without locking or loading the candidate
+        #[arg(long, action=ArgAction::SetTrue)]
+        read_only: bool,
+
+        #[command(flatten)]
+        template: TemplateArgs,
+    },
+
+    /// Shows the session ID and capabilities announced by the router
+    Capabilities,
+
+    /// Terminates another NETCONF or CLI session, e.g. one holding the configuration lock
+    KillSession { session_id: String },
+
+    /// Prints hostname, model, version, serial number and uptime of the router
+    Facts,
+
+    /// Prints operational state as a table
+    Table {
+        #[clap(value_enum)]
+        table: Table,
+
+        /// Only shows interfaces matching this name, e.g. `ge-0/0/*`
+        #[arg(long)]
+        interface: Option<String>,
+    },
+
+    /// Calls a Junos operational RPC by name, e.g. `get-interface-information`
+    Call {
+        name: String,
+
+        /// Argument of the RPC as `name=value`, can be given multiple times
+        #[arg(long = "arg", value_parser = parse_key_value)]
+        args: Vec<(String, String)>,
+
+        /// Flag of the RPC without value, e.g. `terse`, can be given multiple times
+        #[arg(long = "flag")]
+        flags: Vec<String>,
+
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Sends an arbitrary RPC and prints the XML reply
+    Rpc {
+        /// Path to a file with the RPC as XML, e.g. `<get-route-information/>`, or `-` to read from stdin
+        file: String,
+    },
+
+    /// Renders a configuration template locally without connecting to the router
+    Render {
+        /// Path to the configuration template, or `-` to read from stdin
+        local_file: String,
+
+        #[command(flatten)]
+        template: TemplateArgs,
+    },
+}
+
+/// Lays out rows in columns separated by two spaces.
+fn render_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    let mut text = String::new();
+    for row in std::iter::once(headers).chain(rows) {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        text += cells.join("  ").trim_end();
+        text += "\n";
+    }
+    text
+}
+
+/// Parses durations like `90`, `90s`, `5m` or `1h`.
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let (number, unit) = match arg.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => arg.split_at(pos),
+        None => (arg, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration `{}`", arg))?;
+    match unit {
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 3600)),
+        _ => Err(format!("invalid duration `{}`, expected a unit of s, m or h", arg)),
+    }
+}
+
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => Err(format!("expected name=value, got `{}`", arg)),
+    }
+}
+
+/// Reads the given file, or stdin if the path is `-`.
+fn read_input(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut data = String::new();
+        io::stdin().read_to_string(&mut data)?;
+        Ok(data)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Reads the local configuration and renders it as template if any variables were given.
+fn read_config(
+    path: &str,
+    template: &TemplateArgs,
+    hostname: &str,
+    force_render: bool,
+) -> Result<String, Box<dyn Error>> {
+    let data = read_input(path)?;
+    if !force_render && template.vars.is_empty() && template.inventory.is_none() {
+        return Ok(data);
+    }
+
+    let mut context = TemplateContext::new(hostname);
+    let rendered = (|| {
+        if let Some(inventory) = &template.inventory {
+            context.load_inventory(inventory)?;
+        }
+        for vars in &template.vars {
+            context.load_vars(vars)?;
+        }
+        context.render(path, &data)
+    })();
+
+    rendered.map_err(|e| format!("Template rendering failed: {}", e).into())
+}
+
+/// Result of a single run, printed as JSON document with `--output json`.
+#[derive(Serialize)]
+struct Report {
+    host: String,
+    command: &'static str,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+    /// Structured result of commands gathering information
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+    warnings: Vec<RPCError>,
+    errors: Vec<String>,
+    /// Durations of the individual steps in seconds
+    timings: BTreeMap<&'static str, f64>,
+
+    #[serde(skip)]
+    format: OutputFormat,
+}
+
+impl Report {
+    fn new(host: &str, command: &'static str, format: OutputFormat) -> Report {
+        Report {
+            host: host.to_owned(),
+            command,
+            success: false,
+            output: None,
+            diff: None,
+            data: None,
+            warnings: vec![],
+            errors: vec![],
+            timings: BTreeMap::new(),
+            format,
+        }
+    }
+
+    fn output(&mut self, output: String) {
+        match self.format {
+            OutputFormat::Text => print!("{}", output),
+            OutputFormat::JSON => self.output = Some(output),
+        }
+    }
+
+    /// Prints warnings to stderr in text mode, records them in JSON mode, and
+    /// returns the value of the outcome.
+    fn outcome<T>(&mut self, outcome: RpcOutcome<T>) -> T {
+        match self.format {
+            OutputFormat::Text => {
+                for warning in &outcome.warnings {
+                    eprintln!("{}", warning);
+                }
+            }
+            OutputFormat::JSON => self.warnings.extend(outcome.warnings),
+        }
+        outcome.value
+    }
+
+    /// Loads configuration and reports the load outcome.
+    fn load_configuration(
+        &mut self,
+        netconf_session: &mut NETCONFClient,
+        cfg: String,
+        action: &str,
+        format: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let outcome = netconf_session
+            .load_configuration(cfg, action.into(), format.into())
+            .map_err(|e| format!("Config load failed: {}", e))?;
+        if let Some(load_error_count) = self.outcome(outcome) {
+            if self.format == OutputFormat::Text {
+                eprintln!("Load error count: {}", load_error_count.message.trim());
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints `text` in text mode, records `data` in JSON mode.
+    fn data<T: Serialize>(&mut self, text: String, data: &T) -> Result<(), Box<dyn Error>> {
+        match self.format {
+            OutputFormat::Text => print!("{}", text),
+            OutputFormat::JSON => self.data = Some(serde_json::to_value(data)?),
+        }
+        Ok(())
+    }
+
+    /// Shows the diff in text mode if requested, always records it in JSON mode.
+    fn diff(&mut self, cli: &Cli, diff_reply: String, unified: bool) {
+        match self.format {
+            OutputFormat::Text if cli.diff => cli.diff_output.print(&diff_reply, unified),
+            OutputFormat::Text => {}
+            OutputFormat::JSON => self.diff = Some(diff_reply),
+        }
+    }
+
+    fn timing(&mut self, step: &'static str, start: Instant) {
+        self.timings.insert(step, start.elapsed().as_secs_f64());
+    }
+}
+
+/// Sets up logging to stderr. `RUST_LOG` takes precedence over `--verbose`.
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "rucli=info",
+        2 => "rucli=debug",
+        _ => "rucli=trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
 }
 
 fn main() {
     let cli = Cli::parse();
+    init_logging(cli.verbose);
+
+    let command = match cli.command {
+        Commands::Exec { .. } => "exec",
+        Commands::Apply { .. } => "apply",
+        Commands::EditConfig { .. } => "edit-config",
+        Commands::Confirm => "confirm",
+        Commands::Check { .. } => "check",
+        Commands::Capabilities => "capabilities",
+        Commands::KillSession { .. } => "kill-session",
+        Commands::Facts => "facts",
+        Commands::Table { .. } => "table",
+        Commands::Call { .. } => "call",
+        Commands::Rpc { .. } => "rpc",
+        Commands::Render { .. } => "render",
+    };
+    let mut report = Report::new(&cli.hostname, command, cli.output);
+
+    let start = Instant::now();
+    let result = run(&cli, &mut report);
+    report.timing("total", start);
+
+    match result {
+        Ok(()) => report.success = true,
+        Err(e) => report.errors.push(e.to_string()),
+    }
+
+    match report.format {
+        OutputFormat::Text => {
+            for error in &report.errors {
+                eprintln!("{}", error);
+            }
+        }
+        OutputFormat::JSON => println!("{}", serde_json::to_string(&report).unwrap()),
+    }
+
+    if !report.success {
+        process::exit(1);
+    }
+}
+
+fn run(cli: &Cli, report: &mut Report) -> Result<(), Box<dyn Error>> {
+    if let Commands::Render {
+        local_file,
+        template,
+    } = &cli.command
+    {
+        report.output(read_config(local_file, template, &cli.hostname, true)?);
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let transport: Box<dyn Transport> = match &cli.replay {
+        Some(path) => Box::new(ReplayTransport::from_reader(BufReader::new(File::open(path)?))?),
+        None => Box::new(connect_ssh(cli)?),
+    };
+    let transport: Box<dyn Transport> = match &cli.record {
+        Some(path) => {
+            let mut recording = RecordingTransport::new(transport, File::create(path)?);
+            recording.set_redact(!cli.record_unredacted);
+            Box::new(recording)
+        }
+        None => transport,
+    };
+
+    let mut netconf_session = NETCONFClient::new(transport);
+    netconf_session.set_rpc_timeout(cli.timeout.map(Duration::from_secs));
+    netconf_session.set_idle_timeout(cli.idle_timeout.map(Duration::from_secs));
+    netconf_session.set_strict(cli.strict);
+    netconf_session.init()?;
+    report.timing("connect", start);
+
+    let start = Instant::now();
+    let result = run_command(cli, report, &mut netconf_session);
+    report.timing("command", start);
+    result
+}
+
+/// Prepares the configuration database selected with `--db` for changes.
+fn open_database(cli: &Cli, report: &mut Report, netconf_session: &mut NETCONFClient) -> Result<(), Box<dyn Error>> {
+    let wait = cli.lock_wait.unwrap_or_default();
+    if let Some(instance) = &cli.ephemeral_instance {
+        let outcome = netconf_session.retry_while_locked(wait, |s| s.open_ephemeral_instance(instance.clone()))?;
+        report.outcome(outcome);
+        return Ok(());
+    }
+    let database = match cli.db {
+        Database::Shared => {
+            let outcome = netconf_session.retry_while_locked(wait, |s| s.lock_configuration())?;
+            report.outcome(outcome);
+            return Ok(());
+        }
+        Database::Private => ConfigurationDatabase::Private,
+        Database::Exclusive => ConfigurationDatabase::Exclusive,
+    };
+    let outcome = netconf_session.retry_while_locked(wait, |s| s.open_configuration(database))?;
+    report.outcome(outcome);
+    Ok(())
+}
+
+/// Loads configuration with `load` into the database chosen on the command
+/// line and shows the diff. The changes are committed if `commit` holds the
+/// confirm timeout to commit with, and only checked otherwise.
+fn change_configuration<F>(
+    cli: &Cli,
+    report: &mut Report,
+    netconf_session: &mut NETCONFClient,
+    commit: Option<Option<i32>>,
+    load: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut Report, &mut NETCONFClient) -> Result<(), Box<dyn Error>>,
+{
+    // Ephemeral instances keep no rollbacks to compare against.
+    let ephemeral = cli.ephemeral_instance.is_some();
+
+    // Junos refuses to lock or open a candidate holding uncommitted changes,
+    // so they are dealt with first.
+    if !ephemeral {
+        check_candidate(cli, report, netconf_session)?;
+    }
+    open_database(cli, report, netconf_session)?;
+
+    load(report, netconf_session)?;
+
+    if !ephemeral {
+        let diff_reply = netconf_session.diff_configuration("text".to_string())?;
+        report.diff(cli, diff_reply, false);
+    }
+
+    if let Some(confirm_timeout) = commit {
+        eprintln!("Applying configuration...");
+
+        let outcome = netconf_session.apply_configuration(confirm_timeout)?;
+        report.outcome(outcome);
+    }
+
+    close_database(cli, report, netconf_session)
+}
 
-    let ssh_user = match cli.user {
-        Some(user) => user,
+/// Makes sure the candidate holds no changes besides the ones about to be loaded.
+fn check_candidate(cli: &Cli, report: &mut Report, netconf_session: &mut NETCONFClient) -> Result<(), Box<dyn Error>> {
+    let Some(diff) = netconf_session.uncommitted_changes()? else {
+        return Ok(());
+    };
+    if !cli.discard_uncommitted {
+        return Err(format!(
+            "Candidate configuration has uncommitted changes, use --discard-uncommitted to discard them:\n{}",
+            diff.trim_end()
+        )
+        .into());
+    }
+
+    eprintln!("Discarding uncommitted changes:\n{}", diff.trim_end());
+    report.outcome(netconf_session.discard_changes()?);
+    Ok(())
+}
+
+fn close_database(cli: &Cli, report: &mut Report, netconf_session: &mut NETCONFClient) -> Result<(), Box<dyn Error>> {
+    if cli.ephemeral_instance.is_some() {
+        report.outcome(netconf_session.close_configuration()?);
+        return Ok(());
+    }
+    match cli.db {
+        Database::Shared => netconf_session.unlock_configuration()?,
+        Database::Private | Database::Exclusive => report.outcome(netconf_session.close_configuration()?),
+    }
+    Ok(())
+}
+
+fn connect_ssh(cli: &Cli) -> Result<SSHTransport, Box<dyn Error>> {
+    let ssh_user = match &cli.user {
+        Some(user) => user.clone(),
         None => (|| -> Option<String> {
             let mut reader = BufReader::new(
                 File::open(Path::new(
@@ -91,108 +721,298 @@ fn main() {
 
     let mut ssh_connection = SSHConnection::new(
         ssh_user.as_str(),
-        cli.password,
+        cli.password.clone(),
         format!("{}:830", cli.hostname).as_str(),
         cli.debug,
     );
-    ssh_connection.connect().unwrap();
+    ssh_connection.set_connect_timeout(cli.connect_timeout.map(Duration::from_secs));
+    ssh_connection.set_handshake_timeout(cli.handshake_timeout.or(cli.connect_timeout).map(Duration::from_secs));
+    ssh_connection.set_keepalive_interval(cli.keepalive.map(Duration::from_secs));
 
-    let mut netconf_session = NETCONFClient::new(ssh_connection.channel.expect(""));
-    netconf_session.init().unwrap();
+    ssh_connection.connect()?;
+    Ok(ssh_connection.transport().expect(""))
+}
 
-    match cli.command {
-        Commands::Exec { format, command } => {
-            let format_str = match format {
-                Format::Text => "text",
-                Format::JSON => "json",
-            };
+fn run_command(cli: &Cli, report: &mut Report, netconf_session: &mut NETCONFClient) -> Result<(), Box<dyn Error>> {
+    match &cli.command {
+        Commands::Exec {
+            format,
+            stream,
+            commands,
+            batch,
+            command,
+        } => {
+            let format_str = format.as_str();
+
+            if !commands.is_empty() || batch.is_some() {
+                let mut all_commands = vec![];
+                if !command.is_empty() {
+                    all_commands.push(command.join(" "));
+                }
+                all_commands.extend(commands.iter().cloned());
+                if let Some(batch) = batch {
+                    all_commands.extend(
+                        read_input(batch)?
+                            .lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                            .map(str::to_owned),
+                    );
+                }
+
+                let outputs = netconf_session.send_commands(all_commands.clone(), format_str.to_owned())?;
+
+                let mut output = String::new();
+                let mut failed = 0;
+                for (command, result) in all_commands.iter().zip(outputs) {
+                    output += &format!("{}> {}\n", cli.hostname, command);
+                    match result {
+                        Ok(r) => output += &format!("{}\n", report.outcome(r)),
+                        Err(e) => {
+                            failed += 1;
+                            report.errors.push(format!("{}: {}", command, e));
+                        }
+                    }
+                }
+                report.output(output);
+
+                if failed > 0 {
+                    return Err(format!("{} of {} commands failed", failed, all_commands.len()).into());
+                }
+                return Ok(());
+            }
 
             let command_str = command.join(" ").to_owned();
 
-            let r = netconf_session
-                .send_command(command_str, format_str.to_owned())
-                .unwrap();
+            if *stream && report.format == OutputFormat::Text {
+                let mut stdout = io::stdout().lock();
+                let mut reply = netconf_session.stream_command(command_str, format_str.to_owned())?;
+                let outcome = match format {
+                    Format::XML => reply.copy_content(&mut stdout)?,
+                    _ => reply.copy_output(&mut stdout)?,
+                };
+                writeln!(stdout)?;
+                report.outcome(outcome);
+                return Ok(());
+            }
+
+            let r = netconf_session.send_command(command_str, format_str.to_owned())?;
 
-            println!("{}", r);
+            let r = report.outcome(r);
+            report.output(format!("{}\n", r));
         }
         Commands::Apply {
             local_file,
             confirm_timeout,
+            template,
         } => {
-            let data = fs::read_to_string(local_file).unwrap();
-
-            netconf_session.lock_configuration().unwrap();
-
-            if let Err(e) = netconf_session.load_configuration(data, "update".into(), "text".into()) {
-                eprintln!("Config load failed: {}", e);
-                std::process::exit(1);
-            }
-
-            let diff_reply = netconf_session
-                .diff_configuration("text".to_string())
-                .unwrap();
-            if cli.diff {
-                println!("{}", diff_reply);
-            }
-
-            eprintln!("Applying configuration...");
-
-            netconf_session
-                .apply_configuration(confirm_timeout)
-                .unwrap();
-
-            netconf_session.unlock_configuration().unwrap();
+            let data = read_config(local_file, template, &cli.hostname, false)?;
+            change_configuration(cli, report, netconf_session, Some(*confirm_timeout), |report, session| {
+                let format = diff::config_format(&data);
+                report.load_configuration(session, data, diff::load_action(format), format)
+            })?;
         }
         Commands::EditConfig {
             statement,
             confirm_timeout,
         } => {
-            netconf_session.lock_configuration().unwrap();
+            let statement = if statement == "-" {
+                read_input(statement)?
+            } else {
+                statement.clone()
+            };
 
-            for line in statement.split(";") {
-                if let Err(e) = netconf_session.load_configuration(line.into(), "set".into(), "set".into()) {
-                    eprintln!("Config load failed: {}", e);
-                    std::process::exit(1);
+            change_configuration(cli, report, netconf_session, Some(*confirm_timeout), |report, session| {
+                for line in statement.split(";") {
+                    report.load_configuration(session, line.into(), "set", "set")?;
                 }
-            }
-
-            let diff_reply = netconf_session
-                .diff_configuration("text".to_string())
-                .unwrap();
-            if cli.diff {
-                println!("{}", diff_reply);
-            }
-
-            eprintln!("Applying configuration...");
-
-            netconf_session
-                .apply_configuration(confirm_timeout)
-                .unwrap();
-
-            netconf_session.unlock_configuration().unwrap();
+                Ok(())
+            })?;
         }
         Commands::Confirm => {
             eprintln!("Confirming configuration");
 
-            netconf_session.confirm_configuration().unwrap();
+            let outcome = netconf_session.confirm_configuration()?;
+            report.outcome(outcome);
         }
-        Commands::Check { local_file } => {
-            let data = fs::read_to_string(local_file).unwrap();
-
-            let _ = netconf_session.lock_configuration().unwrap();
+        Commands::Check {
+            local_file,
+            read_only: true,
+            template,
+        } => {
+            let data = read_config(local_file, template, &cli.hostname, false)?;
 
-            if let Err(e) = netconf_session.load_configuration(data, "update".into(), "text".into()) {
-                eprintln!("Config load failed: {}", e);
-                std::process::exit(1);
+            let format = diff::config_format(&data);
+            let running = match &cli.ephemeral_instance {
+                Some(instance) => netconf_session.get_ephemeral_configuration(instance.clone(), format.to_string())?,
+                None => netconf_session.get_configuration(format.to_string())?,
+            };
+            let diff_reply = diff::config_diff(&running, &data, format, "committed", local_file);
+            report.diff(cli, diff_reply, true);
+        }
+        Commands::Check {
+            local_file,
+            read_only: false,
+            template,
+        } => {
+            let data = read_config(local_file, template, &cli.hostname, false)?;
+            change_configuration(cli, report, netconf_session, None, |report, session| {
+                let format = diff::config_format(&data);
+                report.load_configuration(session, data, diff::load_action(format), format)
+            })?;
+        }
+        Commands::Capabilities => {
+            #[derive(Serialize)]
+            struct Capabilities<'a> {
+                session_id: Option<&'a str>,
+                capabilities: &'a [String],
             }
 
-            let diff_reply = netconf_session
-                .diff_configuration("text".to_string())
-                .unwrap();
-            if cli.diff {
-                println!("{}", diff_reply);
+            let mut text = format!("Session ID: {}\n", netconf_session.session_id().unwrap_or("-"));
+            for capability in netconf_session.capabilities() {
+                text += &format!("{}\n", capability.trim());
+            }
+            report.data(
+                text,
+                &Capabilities {
+                    session_id: netconf_session.session_id(),
+                    capabilities: netconf_session.capabilities(),
+                },
+            )?;
+        }
+        Commands::KillSession { session_id } => {
+            let outcome = netconf_session.kill_session(session_id.clone())?;
+            report.outcome(outcome);
+        }
+        Commands::Facts => {
+            let facts = report.outcome(facts::gather(netconf_session)?);
+            report.data(facts.to_string(), &facts)?;
+        }
+        Commands::Table { table, interface } => match table {
+            Table::Interfaces => {
+                let interfaces = report.outcome(netconf_session.interfaces(interface.clone())?);
+                let mut rows = vec![];
+                for physical in &interfaces.physical_interfaces {
+                    rows.push(vec![
+                        physical.name.clone(),
+                        physical.admin_status.clone().unwrap_or_default(),
+                        physical.oper_status.clone().unwrap_or_default(),
+                        physical.speed.clone().unwrap_or_default(),
+                        String::new(),
+                        physical.description.clone().unwrap_or_default(),
+                    ]);
+                    for logical in &physical.logical_interfaces {
+                        let addresses: Vec<_> = logical
+                            .address_families
+                            .iter()
+                            .flat_map(|family| &family.addresses)
+                            .filter_map(|address| address.local.clone())
+                            .collect();
+                        rows.push(vec![
+                            logical.name.clone(),
+                            String::new(),
+                            String::new(),
+                            String::new(),
+                            addresses.join(" "),
+                            logical.description.clone().unwrap_or_default(),
+                        ]);
+                    }
+                }
+                let text = render_table(&["Interface", "Admin", "Link", "Speed", "Addresses", "Description"], rows);
+                report.data(text, &interfaces)?;
+            }
+            Table::Bgp => {
+                let summary = report.outcome(netconf_session.bgp_summary()?);
+                let rows = summary
+                    .peers
+                    .iter()
+                    .map(|peer| {
+                        let prefixes: Vec<_> = peer
+                            .ribs
+                            .iter()
+                            .map(|rib| {
+                                format!(
+                                    "{}: {}/{}/{}",
+                                    rib.name,
+                                    rib.active_prefix_count.unwrap_or_default(),
+                                    rib.received_prefix_count.unwrap_or_default(),
+                                    rib.accepted_prefix_count.unwrap_or_default(),
+                                )
+                            })
+                            .collect();
+                        vec![
+                            peer.peer_address.clone(),
+                            peer.peer_as.clone().unwrap_or_default(),
+                            peer.peer_state.clone().unwrap_or_default(),
+                            peer.flap_count.map(|c| c.to_string()).unwrap_or_default(),
+                            prefixes.join(" "),
+                            peer.description.clone().unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                let text = render_table(
+                    &["Peer", "AS", "State", "Flaps", "Active/Received/Accepted", "Description"],
+                    rows,
+                );
+                report.data(text, &summary)?;
+            }
+            Table::Lldp => {
+                let neighbors = report.outcome(netconf_session.lldp_neighbors()?);
+                let rows = neighbors
+                    .neighbors
+                    .iter()
+                    .map(|neighbor| {
+                        vec![
+                            neighbor.local_port.clone().unwrap_or_default(),
+                            neighbor.local_parent_interface.clone().unwrap_or_default(),
+                            neighbor.remote_chassis_id.clone().unwrap_or_default(),
+                            neighbor.remote_port.clone().unwrap_or_default(),
+                            neighbor.remote_system_name.clone().unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                let text = render_table(&["Local", "Parent", "Chassis ID", "Port", "System name"], rows);
+                report.data(text, &neighbors)?;
+            }
+            Table::Routes => {
+                let summary = report.outcome(netconf_session.route_summary()?);
+                let count = |count: Option<u64>| count.map(|c| c.to_string()).unwrap_or_default();
+                let rows = summary
+                    .tables
+                    .iter()
+                    .map(|table| {
+                        vec![
+                            table.name.clone(),
+                            count(table.destination_count),
+                            count(table.total_route_count),
+                            count(table.active_route_count),
+                            count(table.holddown_route_count),
+                            count(table.hidden_route_count),
+                        ]
+                    })
+                    .collect();
+                let text = render_table(&["Table", "Destinations", "Routes", "Active", "Holddown", "Hidden"], rows);
+                report.data(text, &summary)?;
             }
-            let _ = netconf_session.unlock_configuration().unwrap();
+        },
+        Commands::Call {
+            name,
+            args,
+            flags,
+            format,
+        } => {
+            let outcome = netconf_session.call(name, args, flags, format.as_str().to_owned())?;
+            let output = report.outcome(outcome);
+            report.output(output + "\n");
+        }
+        Commands::Rpc { file } => {
+            let rpc = read_input(file)?;
+            let outcome = netconf_session.send_raw_rpc(rpc)?;
+            let reply = report.outcome(outcome);
+            report.output(reply + "\n");
         }
+        Commands::Render { .. } => unreachable!(),
     }
+
+    Ok(())
 }
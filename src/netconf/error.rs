@@ -4,7 +4,9 @@ use crate::netconf::RPCReplyCommand;
 #[derive(Debug, thiserror::Error)]
 pub enum NETCONFError {
     #[error("{0}")]
-    IoError(#[from] std::io::Error),
+    IoError(std::io::Error),
+    #[error("Timed out: {0}")]
+    Timeout(std::io::Error),
     #[error("{0}")]
     XmlError(#[from] quick_xml::Error),
     #[error("{0}")]
@@ -12,9 +14,46 @@ pub enum NETCONFError {
     #[error("Missing OK")]
     MissingOk,
     #[error("Unexpected command: {0}")]
-    UnexpectedCommand(RPCReplyCommand),
+    UnexpectedCommand(Box<RPCReplyCommand>),
     #[error("{0}")]
-    RpcError(#[from] RPCError),
+    RpcError(Box<RPCError>),
+    #[error("Configuration database is locked by {holder}")]
+    LockDenied { holder: String, error: Box<RPCError> },
+    #[error("Invalid XML element name {0:?}")]
+    InvalidName(String),
+    #[error("Unknown elements in reply: {}", .0.join(", "))]
+    UnknownElements(Vec<String>),
+    #[error("Reply with message-id {received:?} does not match request {expected}")]
+    MessageIdMismatch {
+        expected: String,
+        received: Option<String>,
+    },
+}
+
+impl From<std::io::Error> for NETCONFError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => NETCONFError::Timeout(err),
+            _ => NETCONFError::IoError(err),
+        }
+    }
+}
+
+impl From<RPCError> for NETCONFError {
+    fn from(err: RPCError) -> Self {
+        NETCONFError::RpcError(Box::new(err))
+    }
 }
 
 pub type NETCONFResult<T> = Result<T, NETCONFError>;
+
+/// Turns an `rpc-error` denying a lock into `NETCONFError::LockDenied`.
+pub(super) fn lock_denied(error: NETCONFError) -> NETCONFError {
+    match error {
+        NETCONFError::RpcError(error) if error.is_lock_denied() => NETCONFError::LockDenied {
+            holder: error.lock_holder(),
+            error,
+        },
+        other => other,
+    }
+}
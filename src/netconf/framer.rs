@@ -0,0 +1,196 @@
+use std::io::{self, Read};
+
+use memmem::{Searcher, TwoWaySearcher};
+
+/// End-of-message delimiter of the NETCONF 1.0 framing
+pub const DELIMITER: &[u8] = b"]]>]]>";
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Splits the byte stream of a NETCONF session into `]]>]]>` delimited messages.
+///
+/// Bytes read past the end of a message are kept for the next one, and each
+/// byte is only searched for the delimiter once, so large replies are framed
+/// in linear time.
+pub struct Framer {
+    buffer: Vec<u8>,
+    /// Length of the buffer prefix known not to contain the start of a delimiter
+    searched: usize,
+    searcher: TwoWaySearcher<'static>,
+}
+
+impl Default for Framer {
+    fn default() -> Self {
+        Framer::new()
+    }
+}
+
+impl Framer {
+    pub fn new() -> Framer {
+        Framer {
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            searched: 0,
+            searcher: TwoWaySearcher::new(DELIMITER),
+        }
+    }
+
+    /// Reads from `reader` until a complete message is buffered and returns it
+    /// without the delimiter.
+    pub fn read_message<R: Read + ?Sized>(&mut self, reader: &mut R) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(message) = self.next_message() {
+                return Ok(message);
+            }
+            self.fill(reader)?;
+        }
+    }
+
+    /// Appends bytes received by other means than `fill` to the buffer.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete message out of the buffer, if there is one.
+    pub fn next_message(&mut self) -> Option<Vec<u8>> {
+        match self.searcher.search_in(&self.buffer[self.searched..]) {
+            Some(pos) => {
+                let end = self.searched + pos;
+                let rest = self.buffer.split_off(end + DELIMITER.len());
+                let mut message = std::mem::replace(&mut self.buffer, rest);
+                message.truncate(end);
+                self.searched = 0;
+                Some(message)
+            }
+            None => {
+                self.searched = self.buffer.len().saturating_sub(DELIMITER.len() - 1);
+                None
+            }
+        }
+    }
+
+    /// Reads the next bytes of the current message into `buf` without waiting
+    /// for the whole message. Returns 0 once the message is complete, in which
+    /// case the delimiter has been consumed.
+    pub fn read_partial<R: Read + ?Sized>(
+        &mut self,
+        reader: &mut R,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        loop {
+            let available = match self.searcher.search_in(&self.buffer[self.searched..]) {
+                Some(0) if self.searched == 0 => {
+                    self.buffer.drain(..DELIMITER.len());
+                    return Ok(0);
+                }
+                Some(pos) => self.searched + pos,
+                None => {
+                    self.searched = self.buffer.len().saturating_sub(DELIMITER.len() - 1);
+                    self.searched
+                }
+            };
+
+            if available > 0 {
+                let bytes = available.min(buf.len());
+                buf[..bytes].copy_from_slice(&self.buffer[..bytes]);
+                self.buffer.drain(..bytes);
+                self.searched = self.searched.saturating_sub(bytes);
+                return Ok(bytes);
+            }
+            self.fill(reader)?;
+        }
+    }
+
+    /// Appends one read from `reader` to the buffer.
+    fn fill<R: Read + ?Sized>(&mut self, reader: &mut R) -> io::Result<()> {
+        let len = self.buffer.len();
+        self.buffer.resize(len + CHUNK_SIZE, 0);
+        let result = reader.read(&mut self.buffer[len..]);
+        let bytes = *result.as_ref().unwrap_or(&0);
+        self.buffer.truncate(len + bytes);
+
+        if result? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "NETCONF session closed in the middle of a message",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::Framer;
+
+    const MESSAGES: [&str; 3] = ["<rpc-reply>]]>]]</rpc-reply>", "x]]>]]]>]]]>]y", "\n<ok/>\n"];
+
+    /// Reader returning at most `sizes[i]` bytes for its i-th read, cycling through `sizes`
+    struct Chunked<'a> {
+        data: &'a [u8],
+        sizes: Vec<usize>,
+        reads: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let size = self.sizes[self.reads % self.sizes.len()];
+            self.reads += 1;
+            let bytes = size.min(buf.len()).min(self.data.len());
+            buf[..bytes].copy_from_slice(&self.data[..bytes]);
+            self.data = &self.data[bytes..];
+            Ok(bytes)
+        }
+    }
+
+    /// The messages followed by the start of one more, in chunks of 1 to 7 bytes
+    fn readers(stream: &[u8]) -> impl Iterator<Item = Chunked<'_>> {
+        let constant = (1..=7).map(|size| vec![size]);
+        let mixed = (0..7).map(|start| (1..=7).cycle().skip(start).take(7).collect());
+        constant.chain(mixed).map(|sizes| Chunked {
+            data: stream,
+            sizes,
+            reads: 0,
+        })
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut stream = MESSAGES.join("]]>]]>").into_bytes();
+        stream.extend_from_slice(b"]]>]]><rpc-reply>]]");
+        stream
+    }
+
+    #[test]
+    fn reads_messages_in_small_chunks() {
+        let stream = stream();
+        for mut reader in readers(&stream) {
+            let mut framer = Framer::new();
+            for message in MESSAGES {
+                assert_eq!(framer.read_message(&mut reader).unwrap(), message.as_bytes(), "sizes {:?}", reader.sizes);
+            }
+            let error = framer.read_message(&mut reader).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(framer.buffer, b"<rpc-reply>]]");
+        }
+    }
+
+    #[test]
+    fn reads_partial_messages_in_small_chunks() {
+        let stream = stream();
+        for mut reader in readers(&stream) {
+            let mut framer = Framer::new();
+            for message in MESSAGES {
+                let mut received = vec![];
+                let mut buf = [0; 3];
+                loop {
+                    match framer.read_partial(&mut reader, &mut buf).unwrap() {
+                        0 => break,
+                        bytes => received.extend_from_slice(&buf[..bytes]),
+                    }
+                }
+                assert_eq!(received, message.as_bytes(), "sizes {:?}", reader.sizes);
+            }
+        }
+    }
+}
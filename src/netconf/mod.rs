@@ -1,56 +1,257 @@
-use memmem::{Searcher, TwoWaySearcher};
-use std::io::{self, Read, Write};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
 
-use quick_xml::{de::from_str, se::to_string};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::{de::from_str, se::to_string, Reader};
+use tracing::{debug, info, trace, warn};
 
 mod error;
+mod framer;
+pub mod redact;
+mod stream;
+pub mod tables;
+pub mod transcript;
+pub mod transport;
 pub mod xml;
 
-use crate::netconf::error::NETCONFError;
+pub use self::error::NETCONFError;
+pub use self::stream::{ReplyReader, ReplyStream};
+pub use self::transport::Transport;
+
 use crate::netconf::xml::LoadConfigurationResultsEnum;
+use crate::netconf::xml::LoadErrorCount;
+use crate::netconf::xml::RawXml;
+use crate::netconf::xml::RPCError;
 use crate::netconf::xml::RPCReplyCommand;
 use crate::netconf::xml::RPC;
 
 use self::{
-    error::NETCONFResult,
-    xml::{ConfigurationConfirmed, Hello, RPCCommand, RPCReply},
+    error::{lock_denied, NETCONFResult},
+    framer::Framer,
+    redact::redact,
+    transport::TimedReader,
+    xml::{unescape_raw, ConfigurationConfirmed, ConfigurationDatabase, Datastore, Filter, Flag, Hello, RPCCommand, RPCReply},
 };
 
+/// Maximum number of requests `send_commands` has in flight at once
+pub const PIPELINE_DEPTH: usize = 16;
+
+/// Time to wait for the reply to `close-session` when closing on drop without RPC timeout
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delays between attempts of `retry_while_locked`
+const LOCK_RETRY_MIN: Duration = Duration::from_secs(1);
+const LOCK_RETRY_MAX: Duration = Duration::from_secs(16);
+
+/// Request waiting for its reply
+struct PendingRpc {
+    message_id: String,
+    name: &'static str,
+    sent: Instant,
+}
+
+/// Value returned by an RPC together with the warnings the device reported.
+#[derive(Debug)]
+pub struct RpcOutcome<T> {
+    pub value: T,
+    pub warnings: Vec<RPCError>,
+}
+
 pub struct NETCONFClient {
-    // FIXME: Technically, this could be generic.
-    channel: ssh2::Channel,
+    transport: Box<dyn Transport>,
+    framer: Framer,
+
+    rpc_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    /// Point in time the reply to the last request has to be received by
+    deadline: Option<Instant>,
+
+    /// Whether the session is established and not closed yet
+    open: bool,
+    /// Hello received from the device
+    hello: Option<Hello>,
+    /// Whether replies containing unknown elements are rejected
+    strict: bool,
+
+    next_message_id: u64,
+    /// Message IDs of the requests still waiting for a reply, oldest first
+    pending: VecDeque<PendingRpc>,
 }
 
 impl NETCONFClient {
-    pub fn new(channel: ssh2::Channel) -> NETCONFClient {
-        return NETCONFClient { channel };
+    pub fn new<T: Transport + 'static>(transport: T) -> NETCONFClient {
+        return NETCONFClient {
+            transport: Box::new(transport),
+            framer: Framer::new(),
+            rpc_timeout: None,
+            idle_timeout: None,
+            deadline: None,
+            open: false,
+            hello: None,
+            strict: false,
+            next_message_id: 1,
+            pending: VecDeque::new(),
+        };
+    }
+
+    /// Sets how long to wait for the complete reply to a request, including the hello.
+    pub fn set_rpc_timeout(&mut self, timeout: Option<Duration>) {
+        self.rpc_timeout = timeout;
+    }
+
+    /// Sets how long to wait for further data while a reply is being received.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
     }
 
     pub fn init(&mut self) -> NETCONFResult<()> {
         self.send_hello()?;
-        self.read_hello()?;
+        let hello = self.read_hello()?;
+        info!(
+            session_id = hello.session_id.as_deref(),
+            capabilities = hello.capabilities.capability.len(),
+            "NETCONF session established"
+        );
+        self.hello = Some(hello);
+        self.open = true;
 
         return Ok(());
     }
 
-    pub fn read(&mut self) -> io::Result<String> {
-        let mut read_buffer: Vec<u8> = vec![];
+    /// Rejects hellos and replies containing elements unknown to rucli instead
+    /// of ignoring them. Meant for testing against new device releases.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn check_unknown_elements(&self, unknown: Vec<String>) -> NETCONFResult<()> {
+        if self.strict && !unknown.is_empty() {
+            return Err(NETCONFError::UnknownElements(unknown));
+        }
+        Ok(())
+    }
+
+    /// Session ID assigned by the device.
+    pub fn session_id(&self) -> Option<&str> {
+        self.hello.as_ref()?.session_id.as_deref()
+    }
 
-        let mut buffer = [0u8; 128];
-        let search = TwoWaySearcher::new("]]>]]>".as_bytes());
-        while search.search_in(&read_buffer).is_none() {
-            let bytes = self.channel.read(&mut buffer)?;
-            read_buffer.extend(&buffer[..bytes]);
+    /// Capabilities announced by the device.
+    pub fn capabilities(&self) -> &[String] {
+        match &self.hello {
+            Some(hello) => &hello.capabilities.capability,
+            None => &[],
         }
-        let pos = search.search_in(&read_buffer).unwrap();
-        let resp = String::from_utf8(read_buffer[..pos].to_vec()).unwrap();
-        // 6: ]]>]]>
-        read_buffer.drain(0..(pos + 6));
-        Ok(resp)
+    }
+
+    /// Whether the device announced the given capability, ignoring its parameters.
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities()
+            .iter()
+            .any(|c| c.trim().split('?').next() == Some(capability))
+    }
+
+    pub fn supports_base11(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:base:1.1")
+    }
+
+    pub fn supports_candidate(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:candidate:1.0")
+    }
+
+    pub fn supports_confirmed_commit(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:confirmed-commit:1.0")
+            || self.has_capability("urn:ietf:params:netconf:capability:confirmed-commit:1.1")
+    }
+
+    pub fn supports_validate(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:validate:1.0")
+            || self.has_capability("urn:ietf:params:netconf:capability:validate:1.1")
+    }
+
+    pub fn supports_writable_running(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:writable-running:1.0")
+    }
+
+    pub fn supports_rollback_on_error(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:rollback-on-error:1.0")
+    }
+
+    pub fn supports_url(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:url:1.0")
+    }
+
+    pub fn supports_xpath(&self) -> bool {
+        self.has_capability("urn:ietf:params:netconf:capability:xpath:1.0")
+    }
+
+    /// Gracefully closes the session, releasing its locks on the device.
+    /// Called automatically when the client is dropped.
+    pub fn close(&mut self) -> NETCONFResult<()> {
+        if !self.open {
+            return Ok(());
+        }
+        self.open = false;
+        info!(session_id = self.session_id(), "closing NETCONF session");
+
+        let c = RPC::new(RPCCommand::CloseSession {});
+        self.send_rpc(c)?;
+        self.read_ok()?;
+        Ok(())
+    }
+
+    /// Terminates another session, e.g. one holding the configuration lock.
+    pub fn kill_session(&mut self, session_id: String) -> NETCONFResult<RpcOutcome<()>> {
+        info!(session_id, "killing session");
+        let c = RPC::new(RPCCommand::KillSession { session_id });
+        self.send_rpc(c)?;
+        self.read_ok()
+    }
+
+    /// Reads a reply which is expected to contain only `<ok/>`.
+    fn read_ok(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        let mut warnings = vec![];
+        let mut ok = None;
+        for result in self.read_result()? {
+            match result {
+                RPCReplyCommand::RPCError(error) => {
+                    if error.error_severity == "warning" {
+                        warnings.push(error);
+                    } else {
+                        return Err(error.into());
+                    }
+                }
+                RPCReplyCommand::Ok => ok = Some(()),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
+            }
+        }
+        ok.ok_or(NETCONFError::MissingOk)?;
+        Ok(RpcOutcome { value: (), warnings })
+    }
+
+    pub fn read(&mut self) -> io::Result<String> {
+        let mut reader = TimedReader {
+            transport: self.transport.as_mut(),
+            deadline: self.deadline,
+            idle_timeout: self.idle_timeout,
+        };
+        let message = self.framer.read_message(&mut reader)?;
+        let message =
+            String::from_utf8(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        trace!(payload = %redact(&message), "received");
+        Ok(message)
     }
 
     fn write(&mut self, payload: &[u8]) -> io::Result<()> {
-        self.channel.write_all(payload)
+        trace!(payload = %redact(&String::from_utf8_lossy(payload)), "sending");
+        self.deadline = self.rpc_timeout.map(|timeout| Instant::now() + timeout);
+        // The deadline also covers sending, e.g. when the peer stops reading.
+        self.transport.set_write_timeout(self.rpc_timeout)?;
+        let result = self.transport.write_all(payload);
+        self.transport.set_write_timeout(None)?;
+        result
     }
 
     fn send_hello(&mut self) -> NETCONFResult<()> {
@@ -60,49 +261,179 @@ impl NETCONFClient {
             },
             namespace: None,
             session_id: None,
+            extra: Default::default(),
         };
         let hello_xml = to_string(&hello)?;
         let payload_mod = format!("{}\n]]>]]>\n", hello_xml);
-        //println!("{}", payload_mod);
         let wb = self.write(payload_mod.as_bytes())?;
         return Ok(wb);
     }
 
     fn read_hello(&mut self) -> NETCONFResult<Hello> {
         let str = self.read()?;
-        //eprintln!("{}", str);
-        let hello = from_str(&str)?;
+        let hello: Hello = from_str(&str)?;
+        self.check_unknown_elements(hello.unknown_elements())?;
         return Ok(hello);
     }
 
-    fn send_rpc(&mut self, rpc: RPC) -> NETCONFResult<()> {
+    /// Sends the RPC with the next message ID, which is returned.
+    fn send_rpc(&mut self, mut rpc: RPC) -> NETCONFResult<String> {
+        let message_id = self.next_message_id.to_string();
+        self.next_message_id += 1;
+        rpc.message_id = Some(message_id.clone());
+
         let rpc_xml = to_string(&rpc)?;
-        let payload = format!("{}\n]]>]]>\n", rpc_xml).replace("&quot;", "\"");
-        //println!("{}", payload);
-        let wb = self.write(payload.as_bytes())?;
-        return Ok(wb);
+        let rpc_xml = unescape_raw(&rpc_xml).map_err(quick_xml::Error::from)?;
+        let payload = format!("{}\n]]>]]>\n", rpc_xml);
+        debug!(message_id, rpc = rpc.rpc.name(), "sending RPC");
+        self.write(payload.as_bytes())?;
+        self.pending.push_back(PendingRpc {
+            message_id: message_id.clone(),
+            name: rpc.rpc.name(),
+            sent: Instant::now(),
+        });
+        Ok(message_id)
+    }
+
+    /// Checks that a reply belongs to the oldest request still waiting for one.
+    fn check_message_id(&mut self, received: Option<&str>) -> NETCONFResult<()> {
+        let (expected, name, sent) = match self.pending.pop_front() {
+            Some(pending) => (pending.message_id, pending.name, pending.sent),
+            None => (String::new(), "(none)", Instant::now()),
+        };
+        if received != Some(expected.as_str()) {
+            warn!(expected, received, "reply does not match request");
+            return Err(NETCONFError::MessageIdMismatch {
+                expected,
+                received: received.map(str::to_owned),
+            });
+        }
+        debug!(
+            message_id = expected,
+            rpc = name,
+            elapsed_ms = sent.elapsed().as_millis() as u64,
+            "received reply"
+        );
+        Ok(())
     }
 
     fn read_result(&mut self) -> NETCONFResult<impl Iterator<Item = RPCReplyCommand>> {
         let str = self.read()?;
-        //eprintln!("{}", str);
-        Ok(from_str::<RPCReply>(&str)?.rpc_reply.into_iter())
+        // Matched before parsing, so a malformed reply doesn't leave its
+        // request pending and every following reply mismatched.
+        self.check_message_id(reply_message_id(&str).as_deref())?;
+        let reply = from_str::<RPCReply>(&str)?;
+        self.check_unknown_elements(reply.unknown_elements())?;
+        // Unknown elements were reported above in strict mode, and are ignored otherwise.
+        Ok(reply
+            .rpc_reply
+            .into_iter()
+            .filter(|result| !matches!(result, RPCReplyCommand::Unknown { .. })))
+    }
+
+    pub fn send_command(&mut self, command: String, format: String) -> NETCONFResult<RpcOutcome<String>> {
+        let c = RPC::new(RPCCommand::Command {
+            command,
+            format: format.clone(),
+        });
+        let _ = self.send_rpc(c)?;
+        self.read_output(&format)
     }
 
-    pub fn send_command(&mut self, command: String, format: String) -> NETCONFResult<String> {
-        let c = RPC {
-            rpc: RPCCommand::Command {
+    /// Calls a Junos operational RPC by name, e.g. `get-interface-information`
+    /// with the argument `interface-name` and the flag `terse`, and returns its
+    /// output in the given format (`text`, `json` or `xml`).
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: &[(String, String)],
+        flags: &[String],
+        format: String,
+    ) -> NETCONFResult<RpcOutcome<String>> {
+        let names = std::iter::once(name)
+            .chain(args.iter().map(|(key, _)| key.as_str()))
+            .chain(flags.iter().map(String::as_str));
+        for name in names {
+            if !xml::is_element_name(name) {
+                return Err(NETCONFError::InvalidName(name.to_owned()));
+            }
+        }
+
+        let mut rpc = format!("<{} format=\"{}\">", name, escape(&format));
+        for (key, value) in args {
+            rpc += &format!("<{}>{}</{}>", key, escape(value), key);
+        }
+        for flag in flags {
+            rpc += &format!("<{}/>", flag);
+        }
+        rpc += &format!("</{}>", name);
+
+        self.send_rpc(RPC::new(RPCCommand::Raw(RawXml(rpc))))?;
+        self.read_output(&format)
+    }
+
+    /// Sends several commands without waiting for the individual replies and
+    /// returns their outputs in the same order.
+    ///
+    /// Up to `PIPELINE_DEPTH` requests are in flight at once. Errors reported
+    /// by the device only fail the affected command, while transport and
+    /// parsing errors abort the whole batch.
+    pub fn send_commands(
+        &mut self,
+        commands: Vec<String>,
+        format: String,
+    ) -> NETCONFResult<Vec<NETCONFResult<RpcOutcome<String>>>> {
+        let count = commands.len();
+        let mut commands = commands.into_iter();
+        let mut outputs = Vec::with_capacity(count);
+
+        for command in commands.by_ref().take(PIPELINE_DEPTH) {
+            self.send_rpc(RPC::new(RPCCommand::Command {
                 command,
                 format: format.clone(),
-            },
-        };
-        let _ = self.send_rpc(c)?;
+            }))?;
+        }
+        while outputs.len() < count {
+            match self.read_output(&format) {
+                Err(
+                    e @ (NETCONFError::RpcError(_)
+                    | NETCONFError::UnexpectedCommand(_)
+                    | NETCONFError::MissingOk),
+                ) => outputs.push(Err(e)),
+                output => outputs.push(Ok(output?)),
+            }
+
+            if let Some(command) = commands.next() {
+                self.send_rpc(RPC::new(RPCCommand::Command {
+                    command,
+                    format: format.clone(),
+                }))?;
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Reads the reply to a command or operational RPC requested in `format`.
+    fn read_output(&mut self, format: &str) -> NETCONFResult<RpcOutcome<String>> {
+        if format == "xml" {
+            return ReplyStream::new(ReplyReader::new(self)).read_content();
+        }
+        let results = self.read_result()?;
+        self.command_output(results, format)
+    }
+
+    fn command_output(
+        &mut self,
+        results: impl Iterator<Item = RPCReplyCommand>,
+        format: &str,
+    ) -> NETCONFResult<RpcOutcome<String>> {
+        let mut warnings = vec![];
         let mut output = None;
-        for result in self.read_result()? {
+        for result in results {
             match result {
                 RPCReplyCommand::RPCError(error) => {
                     if error.error_severity == "warning" {
-                        eprintln!("{}", error);
+                        warnings.push(error);
                     } else {
                         return Err(error.into());
                     }
@@ -113,95 +444,170 @@ impl NETCONFClient {
                 RPCReplyCommand::Output { text } if output.is_none() && format == "text" => {
                     output = Some(text)
                 }
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
-        output.ok_or(NETCONFError::MissingOk)
+        Ok(RpcOutcome {
+            value: output.ok_or(NETCONFError::MissingOk)?,
+            warnings,
+        })
     }
 
-    pub fn lock_configuration(&mut self) -> NETCONFResult<()> {
-        let c = RPC {
-            rpc: RPCCommand::LockConfiguration {},
-        };
+    /// Sends a command and returns its reply as a stream instead of reading it
+    /// completely, for outputs too large to be held in memory.
+    pub fn stream_command(&mut self, command: String, format: String) -> NETCONFResult<ReplyStream<'_>> {
+        let c = RPC::new(RPCCommand::Command { command, format: format.clone() });
+        self.send_rpc(c)?;
+        Ok(ReplyStream::new(ReplyReader::new(self)).with_format(&format))
+    }
+
+    pub fn lock_configuration(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        let c = RPC::new(RPCCommand::LockConfiguration {});
         let _ = self.send_rpc(c)?;
-        for result in self.read_result()? {
-            match result {
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+        self.read_optional_ok().map_err(lock_denied)
+    }
+
+    /// Runs `lock` until it succeeds, retrying with exponential backoff for at
+    /// most `wait` while it fails with `NETCONFError::LockDenied`.
+    pub fn retry_while_locked<T>(
+        &mut self,
+        wait: Duration,
+        mut lock: impl FnMut(&mut Self) -> NETCONFResult<T>,
+    ) -> NETCONFResult<T> {
+        let deadline = Instant::now() + wait;
+        let mut backoff = LOCK_RETRY_MIN;
+        loop {
+            match lock(self) {
+                Err(NETCONFError::LockDenied { holder, .. }) if Instant::now() < deadline => {
+                    let delay = backoff.min(deadline.saturating_duration_since(Instant::now()));
+                    info!(holder, ?delay, "configuration database locked, retrying");
+                    std::thread::sleep(delay);
+                    backoff = (backoff * 2).min(LOCK_RETRY_MAX);
+                }
+                result => return result,
             }
         }
-        Ok(())
     }
 
     pub fn unlock_configuration(&mut self) -> NETCONFResult<()> {
-        let c = RPC {
-            rpc: RPCCommand::UnlockConfiguration {},
-        };
+        let c = RPC::new(RPCCommand::UnlockConfiguration {});
         let _ = self.send_rpc(c)?;
         for result in self.read_result()? {
             match result {
                 RPCReplyCommand::Ok => {} // sometimes sent, sometimes not
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
         Ok(())
     }
 
-    pub fn apply_configuration(&mut self, confirm_timeout: Option<i32>) -> NETCONFResult<()> {
+    /// Opens the private or exclusive configuration database instead of using
+    /// the shared candidate. Uncommitted changes are discarded when it is
+    /// closed again.
+    pub fn open_configuration(&mut self, database: ConfigurationDatabase) -> NETCONFResult<RpcOutcome<()>> {
+        let c = RPC::new(RPCCommand::OpenConfiguration {
+            private: (database == ConfigurationDatabase::Private).then_some(Flag {}),
+            exclusive: (database == ConfigurationDatabase::Exclusive).then_some(Flag {}),
+            ephemeral_instance: None,
+        });
+        self.send_rpc(c)?;
+        self.read_optional_ok().map_err(lock_denied)
+    }
+
+    /// Opens an instance of the ephemeral configuration database. Loading and
+    /// committing configuration then applies to this instance until it is closed.
+    pub fn open_ephemeral_instance(&mut self, instance: String) -> NETCONFResult<RpcOutcome<()>> {
+        let c = RPC::new(RPCCommand::OpenConfiguration {
+            private: None,
+            exclusive: None,
+            ephemeral_instance: Some(instance),
+        });
+        self.send_rpc(c)?;
+        self.read_optional_ok()
+    }
+
+    pub fn close_configuration(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::CloseConfiguration {}))?;
+        self.read_optional_ok()
+    }
+
+    /// Reads a reply that may consist of `<ok/>`, warnings or nothing at all.
+    fn read_optional_ok(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        let mut warnings = vec![];
+        for result in self.read_result()? {
+            match result {
+                RPCReplyCommand::RPCError(error) if error.error_severity == "warning" => {
+                    warnings.push(error)
+                }
+                RPCReplyCommand::RPCError(error) => return Err(error.into()),
+                RPCReplyCommand::Ok => {}
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
+            }
+        }
+        Ok(RpcOutcome { value: (), warnings })
+    }
+
+    pub fn apply_configuration(&mut self, confirm_timeout: Option<i32>) -> NETCONFResult<RpcOutcome<()>> {
         if let Some(confirm_timeout) = confirm_timeout {
-            let c = RPC {
-                rpc: RPCCommand::CommitConfirmedConfiguration {
-                    confirm_timeout,
-                    confirmed: ConfigurationConfirmed {},
-                },
-            };
+            let c = RPC::new(RPCCommand::CommitConfirmedConfiguration {
+                confirm_timeout,
+                confirmed: ConfigurationConfirmed {},
+            });
             let _ = self.send_rpc(c)?;
         } else {
-            let c = RPC {
-                rpc: RPCCommand::CommitConfiguration {},
-            };
+            let c = RPC::new(RPCCommand::CommitConfiguration {});
             let _ = self.send_rpc(c)?;
         }
+        let mut warnings = vec![];
         let mut ok = None;
         for result in self.read_result()? {
             match result {
                 RPCReplyCommand::RPCError(error) => {
                     if error.error_severity == "warning" {
-                        eprintln!("{}", error);
+                        warnings.push(error);
                     } else {
                         return Err(error.into());
                     }
                 }
                 RPCReplyCommand::Other(_) => {} // ???
                 RPCReplyCommand::Ok => ok = Some(()),
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
-        ok.ok_or(NETCONFError::MissingOk)
+        ok.ok_or(NETCONFError::MissingOk)?;
+        Ok(RpcOutcome { value: (), warnings })
     }
 
-    pub fn confirm_configuration(&mut self) -> NETCONFResult<()> {
-        let c = RPC {
-            rpc: RPCCommand::CommitConfiguration {},
-        };
+    pub fn confirm_configuration(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        let c = RPC::new(RPCCommand::CommitConfiguration {});
         let _ = self.send_rpc(c)?;
+        let mut warnings = vec![];
         let mut ok = None;
         for result in self.read_result()? {
             match result {
                 RPCReplyCommand::RPCError(error) => {
                     if error.error_severity == "warning" {
-                        eprintln!("{}", error);
+                        warnings.push(error);
                     } else {
                         return Err(error.into());
                     }
                 }
                 RPCReplyCommand::Ok => ok = Some(()),
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
-        ok.ok_or(NETCONFError::MissingOk)
+        ok.ok_or(NETCONFError::MissingOk)?;
+        Ok(RpcOutcome { value: (), warnings })
     }
 
-    pub fn load_configuration(&mut self, cfg: String, action: String, format: String) -> NETCONFResult<()> {
+    /// Loads configuration into the candidate. The outcome carries the number
+    /// of errors Junos reported while loading, if any.
+    pub fn load_configuration(
+        &mut self,
+        cfg: String,
+        action: String,
+        format: String,
+    ) -> NETCONFResult<RpcOutcome<Option<LoadErrorCount>>> {
         let mut cfg_text = None;
         let mut cfg_set = None;
         match format.as_str() {
@@ -209,14 +615,12 @@ impl NETCONFClient {
             "set" => cfg_set = Some(cfg),
             _ => unimplemented!(),
         }
-        let c = RPC {
-            rpc: RPCCommand::LoadConfiguration {
-                format,
-                action,
-                cfg_text,
-                cfg_set,
-            },
-        };
+        let c = RPC::new(RPCCommand::LoadConfiguration {
+            format,
+            action,
+            cfg_text,
+            cfg_set,
+        });
         let _ = self.send_rpc(c)?;
 
         let mut load_config_result = None;
@@ -225,9 +629,11 @@ impl NETCONFClient {
                 RPCReplyCommand::LoadConfigurationResults(results) => {
                     load_config_result = Some(results);
                 }
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
+        let mut warnings = vec![];
+        let mut load_error_count = None;
         let mut ok = None;
         for result in load_config_result
             .ok_or(NETCONFError::MissingOk)?
@@ -236,28 +642,31 @@ impl NETCONFClient {
             match result {
                 LoadConfigurationResultsEnum::RPCError(error) => {
                     if error.error_severity == "warning" {
-                        eprintln!("{}", error);
+                        warnings.push(error);
                     } else {
                         return Err(error.into());
                     }
                 }
-                LoadConfigurationResultsEnum::LoadErrorCount(l) => {
-                    eprintln!("{:?}", l);
-                }
+                LoadConfigurationResultsEnum::LoadErrorCount(l) => load_error_count = Some(l),
                 LoadConfigurationResultsEnum::Ok => ok = Some(()),
+                LoadConfigurationResultsEnum::Unknown { .. } => {}
             }
         }
-        ok.ok_or(NETCONFError::MissingOk)
+        ok.ok_or(NETCONFError::MissingOk)?;
+        Ok(RpcOutcome {
+            value: load_error_count,
+            warnings,
+        })
     }
 
     pub fn diff_configuration(&mut self, format: String) -> NETCONFResult<String> {
-        let c = RPC {
-            rpc: RPCCommand::GetConfiguration {
-                format: format,
-                rollback: Some("0".to_string()),
-                compare: Some("rollback".to_string()),
-            },
-        };
+        let c = RPC::new(RPCCommand::GetConfiguration {
+            format: format,
+            database: None,
+            rollback: Some("0".to_string()),
+            compare: Some("rollback".to_string()),
+            ephemeral_instance: None,
+        });
         let _ = self.send_rpc(c)?;
         let mut diff_result = None;
         for result in self.read_result()? {
@@ -267,9 +676,211 @@ impl NETCONFClient {
                 } => {
                     diff_result = Some(configuration_output);
                 }
-                other => return Err(NETCONFError::UnexpectedCommand(other)),
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
             }
         }
         diff_result.ok_or(NETCONFError::MissingOk)
     }
+
+    /// Returns the diff of the candidate against the committed configuration if
+    /// it contains uncommitted changes, e.g. left behind by an aborted run.
+    pub fn uncommitted_changes(&mut self) -> NETCONFResult<Option<String>> {
+        let diff = self.diff_configuration("text".to_string())?;
+        Ok(Some(diff).filter(|diff| !diff.trim().is_empty()))
+    }
+
+    /// Fetches the committed configuration in the given format (`text` or `set`).
+    pub fn get_configuration(&mut self, format: String) -> NETCONFResult<String> {
+        let c = RPC::new(RPCCommand::GetConfiguration {
+            format,
+            database: Some("committed".to_string()),
+            rollback: None,
+            compare: None,
+            ephemeral_instance: None,
+        });
+        self.send_rpc(c)?;
+        self.read_configuration()
+    }
+
+    /// Fetches the committed configuration of an ephemeral database instance.
+    pub fn get_ephemeral_configuration(&mut self, instance: String, format: String) -> NETCONFResult<String> {
+        let c = RPC::new(RPCCommand::GetConfiguration {
+            format,
+            database: Some("ephemeral".to_string()),
+            rollback: None,
+            compare: None,
+            ephemeral_instance: Some(instance),
+        });
+        self.send_rpc(c)?;
+        self.read_configuration()
+    }
+
+    fn read_configuration(&mut self) -> NETCONFResult<String> {
+        let mut configuration = None;
+        for result in self.read_result()? {
+            match result {
+                RPCReplyCommand::ConfigurationText(text) | RPCReplyCommand::ConfigurationSet(text) => {
+                    configuration = Some(text.text);
+                }
+                other => return Err(NETCONFError::UnexpectedCommand(Box::new(other))),
+            }
+        }
+        configuration.ok_or(NETCONFError::MissingOk)
+    }
+
+    /// Sends an RPC given as XML, e.g. `<get-route-information/>`, and returns
+    /// the content of the reply as XML.
+    pub fn send_raw_rpc(&mut self, rpc: String) -> NETCONFResult<RpcOutcome<String>> {
+        self.send_rpc(RPC::new(RPCCommand::Raw(RawXml(rpc.trim().to_string()))))?;
+        ReplyStream::new(ReplyReader::new(self)).read_content()
+    }
+
+    /// Retrieves state and configuration data as XML (RFC 6241 `get`).
+    pub fn get(&mut self, filter: Option<Filter>) -> NETCONFResult<RpcOutcome<String>> {
+        self.send_rpc(RPC::new(RPCCommand::Get { filter }))?;
+        ReplyStream::new(ReplyReader::new(self)).read_data()
+    }
+
+    /// Retrieves the configuration of a datastore as XML (RFC 6241 `get-config`).
+    pub fn get_config(&mut self, source: Datastore, filter: Option<Filter>) -> NETCONFResult<RpcOutcome<String>> {
+        self.send_rpc(RPC::new(RPCCommand::GetConfig {
+            source: source.into(),
+            filter,
+        }))?;
+        ReplyStream::new(ReplyReader::new(self)).read_data()
+    }
+
+    /// Loads the XML configuration `config` into a datastore (RFC 6241 `edit-config`).
+    ///
+    /// The options take the values defined by RFC 6241, e.g. `merge`, `replace`
+    /// or `none` for `default_operation`, `test-then-set`, `set` or `test-only`
+    /// for `test_option` and `stop-on-error`, `continue-on-error` or
+    /// `rollback-on-error` for `error_option`.
+    pub fn edit_config(
+        &mut self,
+        target: Datastore,
+        config: String,
+        default_operation: Option<String>,
+        test_option: Option<String>,
+        error_option: Option<String>,
+    ) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::EditConfig {
+            target: target.into(),
+            default_operation,
+            test_option,
+            error_option,
+            config: RawXml(config),
+        }))?;
+        self.read_ok()
+    }
+
+    /// Replaces the content of `target` with the one of `source`.
+    pub fn copy_config(&mut self, target: Datastore, source: Datastore) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::CopyConfig {
+            target: target.into(),
+            source: source.into(),
+        }))?;
+        self.read_ok()
+    }
+
+    pub fn delete_config(&mut self, target: Datastore) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::DeleteConfig { target: target.into() }))?;
+        self.read_ok()
+    }
+
+    pub fn lock(&mut self, target: Datastore) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::Lock { target: target.into() }))?;
+        self.read_ok().map_err(lock_denied)
+    }
+
+    pub fn unlock(&mut self, target: Datastore) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::Unlock { target: target.into() }))?;
+        self.read_ok()
+    }
+
+    pub fn validate(&mut self, source: Datastore) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::Validate { source: source.into() }))?;
+        self.read_ok()
+    }
+
+    /// Commits the candidate configuration (RFC 6241 `commit`).
+    pub fn commit(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::Commit {}))?;
+        self.read_ok()
+    }
+
+    /// Reverts the candidate configuration to the running one.
+    pub fn discard_changes(&mut self) -> NETCONFResult<RpcOutcome<()>> {
+        self.send_rpc(RPC::new(RPCCommand::DiscardChanges {}))?;
+        self.read_ok()
+    }
+}
+
+/// Reads the message-id of a reply from its `rpc-reply` start tag without
+/// parsing the rest of it.
+fn reply_message_id(reply: &str) -> Option<String> {
+    let mut reader = Reader::from_str(reply);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) | Event::Empty(start) if start.local_name().as_ref() == b"rpc-reply" => {
+                let message_id = start.try_get_attribute("message-id").ok()??;
+                return Some(message_id.unescape_value().ok()?.into_owned());
+            }
+            Event::Start(_) | Event::Empty(_) | Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+impl Drop for NETCONFClient {
+    fn drop(&mut self) {
+        if self.open {
+            if self.rpc_timeout.is_none() {
+                self.rpc_timeout = Some(CLOSE_TIMEOUT);
+            }
+            let _ = self.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::transcript::{Direction, ReplayTransport, TranscriptEntry};
+    use super::NETCONFClient;
+
+    fn received(message: &str) -> TranscriptEntry {
+        TranscriptEntry {
+            timestamp: 0.0,
+            direction: Direction::Received,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn malformed_reply_is_matched_to_its_request() {
+        let transport = ReplayTransport::new(vec![
+            received("<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities></hello>"),
+            received("<rpc-reply message-id=\"1\"><output>r1</oops></rpc-reply>"),
+            received("<rpc-reply message-id=\"2\"><output>r2</output></rpc-reply>"),
+        ]);
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+
+        assert!(client.send_command("show version".into(), "text".into()).is_err());
+        let outcome = client.send_command("show version".into(), "text".into()).unwrap();
+        assert_eq!(outcome.value, "r2");
+    }
+
+    #[test]
+    fn lock_returns_warnings() {
+        let transport = ReplayTransport::new(vec![
+            received("<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities></hello>"),
+            received("<rpc-reply message-id=\"1\"><rpc-error><error-severity>warning</error-severity><error-message>uncommitted changes will be discarded on exit</error-message></rpc-error><ok/></rpc-reply>"),
+        ]);
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+
+        let outcome = client.lock_configuration().unwrap();
+        assert_eq!(outcome.warnings.len(), 1);
+    }
 }
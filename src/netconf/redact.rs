@@ -0,0 +1,186 @@
+//! Redaction of secrets in NETCONF payloads before they are logged.
+
+use std::borrow::Cow;
+
+/// Placeholder replacing redacted secrets, free of markup so redacted
+/// messages still parse
+pub const REDACTED: &str = "REDACTED";
+
+/// Configuration statements whose value is a secret
+const SECRET_KEYWORDS: &[&str] = &[
+    "ascii-text",
+    "authentication-key",
+    "community",
+    "encrypted-password",
+    "hello-authentication-key",
+    "hexadecimal",
+    "key",
+    "md5-key",
+    "password",
+    "pre-shared-key",
+    "secret",
+    "simple-password",
+];
+
+/// Statements introducing the encoding of a secret, e.g.
+/// `pre-shared-key ascii-text "secret";`, whose value is redacted on its own
+const ENCODING_KEYWORDS: &[&str] = &["ascii-text", "hexadecimal"];
+
+/// Secret statements which are named lists in XML, e.g.
+/// `<community><name>secret</name>...</community>`
+const NAMED_KEYWORDS: &[&str] = &["community"];
+
+/// Prefixes of Junos `$9$` and crypt(3) style password hashes
+const HASH_PREFIXES: &[&str] = &["$1$", "$5$", "$6$", "$8$", "$9$"];
+
+/// Removes secrets from a NETCONF payload.
+///
+/// Redacts password hashes like `$9$...` or `$6$...` anywhere in the payload,
+/// and the values of secret statements like `encrypted-password` or SNMP
+/// communities in XML, curly-brace and set configuration.
+pub fn redact(payload: &str) -> Cow<'_, str> {
+    let mut output = redact_hashes(payload);
+    for keyword in SECRET_KEYWORDS {
+        if output.contains(keyword) {
+            output = Cow::Owned(redact_keyword(&output, keyword));
+        }
+    }
+    output
+}
+
+fn redact_hashes(payload: &str) -> Cow<'_, str> {
+    if !HASH_PREFIXES.iter().any(|prefix| payload.contains(prefix)) {
+        return Cow::Borrowed(payload);
+    }
+
+    let mut output = String::with_capacity(payload.len());
+    let mut rest = payload;
+    while let Some(start) = HASH_PREFIXES.iter().filter_map(|p| rest.find(p)).min() {
+        output.push_str(&rest[..start]);
+        output.push_str(REDACTED);
+        rest = &rest[start..];
+        let end = rest.find(is_value_end).unwrap_or(rest.len());
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    Cow::Owned(output)
+}
+
+/// Redacts the value following `keyword`, either as XML element content or as
+/// statement value in text and set configuration.
+fn redact_keyword(payload: &str, keyword: &str) -> String {
+    let mut output = String::with_capacity(payload.len());
+    let mut rest = payload;
+    while let Some(pos) = rest.find(keyword) {
+        let before = &rest[..pos];
+        let after = &rest[pos + keyword.len()..];
+        output.push_str(before);
+        output.push_str(keyword);
+        rest = after;
+
+        // Only match whole words, e.g. not `key` in `keychain`.
+        let prev = before.chars().last();
+        let is_word = !matches!(prev, Some(c) if c.is_alphanumeric() || c == '-' || c == '_');
+        if !is_word {
+            continue;
+        }
+
+        if prev == Some('<') && after.starts_with('>') {
+            // <keyword>value</keyword> or <keyword><name>value</name>
+            let mut open = ">";
+            let mut content = &after[1..];
+            if NAMED_KEYWORDS.contains(&keyword) {
+                if let Some(name) = content.strip_prefix("<name>") {
+                    open = "><name>";
+                    content = name;
+                }
+            }
+            if let Some(end) = content.find('<') {
+                if end > 0 {
+                    output.push_str(open);
+                    output.push_str(REDACTED);
+                    rest = &content[end..];
+                }
+            }
+        } else if let Some(value) = after.strip_prefix(' ') {
+            // The value following the encoding is redacted on its own.
+            let next = value.split(is_value_end).next().unwrap_or_default();
+            if ENCODING_KEYWORDS.contains(&next) {
+                continue;
+            }
+
+            // keyword "value"; or keyword value;
+            let end = match value.strip_prefix('"') {
+                Some(quoted) => quoted.find('"').map(|end| end + 2),
+                None => Some(value.find(is_value_end).unwrap_or(value.len())),
+            };
+            if let Some(end) = end.filter(|end| *end > 0) {
+                if !value[..end].starts_with('{') {
+                    output.push(' ');
+                    output.push_str(REDACTED);
+                    rest = &value[end..];
+                }
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn is_value_end(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | ';' | '<' | '\'' | '&')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+
+    #[test]
+    fn redacts_xml() {
+        assert_eq!(
+            redact("<encrypted-password>$6$abc</encrypted-password>"),
+            "<encrypted-password>REDACTED</encrypted-password>"
+        );
+        assert_eq!(
+            redact("<pre-shared-key><ascii-text>plaintext</ascii-text></pre-shared-key>"),
+            "<pre-shared-key><ascii-text>REDACTED</ascii-text></pre-shared-key>"
+        );
+        assert_eq!(
+            redact("<community><name>s3cret</name><authorization>read-only</authorization></community>"),
+            "<community><name>REDACTED</name><authorization>read-only</authorization></community>"
+        );
+    }
+
+    #[test]
+    fn redacts_text() {
+        assert_eq!(
+            redact("pre-shared-key ascii-text \"plaintext\";"),
+            "pre-shared-key ascii-text REDACTED;"
+        );
+        assert_eq!(
+            redact("pre-shared-key hexadecimal 0123abcd;"),
+            "pre-shared-key hexadecimal REDACTED;"
+        );
+        assert_eq!(
+            redact("community s3cret {\n    authorization read-only;\n}"),
+            "community REDACTED {\n    authorization read-only;\n}"
+        );
+        assert_eq!(redact("keychain foo;"), "keychain foo;");
+    }
+
+    #[test]
+    fn redacts_set() {
+        assert_eq!(
+            redact("set snmp community s3cret authorization read-only"),
+            "set snmp community REDACTED authorization read-only"
+        );
+        assert_eq!(
+            redact("set security ike policy p pre-shared-key ascii-text plaintext"),
+            "set security ike policy p pre-shared-key ascii-text REDACTED"
+        );
+        assert_eq!(
+            redact("set system root-authentication encrypted-password \"$6$salt$hash\""),
+            "set system root-authentication encrypted-password REDACTED"
+        );
+    }
+}
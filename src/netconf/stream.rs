@@ -0,0 +1,484 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use quick_xml::de::from_str;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use tracing::trace;
+
+use super::error::NETCONFResult;
+use super::redact::redact;
+use super::transport::TimedReader;
+use super::xml::RPCError;
+use super::{NETCONFClient, RpcOutcome};
+
+/// Largest piece of text `ReplyStream::copy_text` writes at once
+pub const TEXT_CHUNK: usize = 8192;
+
+/// Longest entity reference unescaped, e.g. `&#x10FFFF;`
+const MAX_ENTITY: usize = 10;
+
+/// Reads the raw bytes of a single reply as they arrive from the device.
+///
+/// Dropping the reader before the end of the reply discards the remainder of
+/// it, so the session stays usable for further RPCs.
+pub struct ReplyReader<'a> {
+    client: &'a mut NETCONFClient,
+    done: bool,
+}
+
+impl<'a> ReplyReader<'a> {
+    pub(super) fn new(client: &'a mut NETCONFClient) -> ReplyReader<'a> {
+        ReplyReader {
+            client,
+            done: false,
+        }
+    }
+}
+
+impl Read for ReplyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let client = &mut *self.client;
+        let mut reader = TimedReader {
+            transport: client.transport.as_mut(),
+            deadline: client.deadline,
+            idle_timeout: client.idle_timeout,
+        };
+        let bytes = client.framer.read_partial(&mut reader, buf)?;
+        trace!(payload = %redact(&String::from_utf8_lossy(&buf[..bytes])), "received chunk");
+        self.done = bytes == 0;
+        Ok(bytes)
+    }
+}
+
+impl Drop for ReplyReader<'_> {
+    fn drop(&mut self) {
+        let mut buf = [0u8; 8192];
+        while let Ok(1..) = self.read(&mut buf) {}
+    }
+}
+
+/// Incrementally parsed reply, yielding XML events without holding the whole
+/// reply in memory.
+pub struct ReplyStream<'a> {
+    reader: Reader<BufReader<ReplyReader<'a>>>,
+    buf: Vec<u8>,
+    /// Whether the message-id of the `rpc-reply` has been checked
+    checked: bool,
+    /// Whether the output is the bare text of the reply, as for JSON
+    bare_output: bool,
+}
+
+impl<'a> ReplyStream<'a> {
+    pub(super) fn new(reader: ReplyReader<'a>) -> ReplyStream<'a> {
+        ReplyStream {
+            reader: Reader::from_reader(BufReader::with_capacity(TEXT_CHUNK, reader)),
+            buf: vec![],
+            checked: false,
+            bare_output: false,
+        }
+    }
+
+    /// Sets the format requested for the output, which decides where
+    /// `copy_output` finds it.
+    pub(super) fn with_format(mut self, format: &str) -> ReplyStream<'a> {
+        self.bare_output = format == "json";
+        self
+    }
+
+    /// Returns the next XML event of the reply, `Event::Eof` at its end.
+    pub fn next_event(&mut self) -> NETCONFResult<Event<'_>> {
+        self.buf.clear();
+        let event = self.reader.read_event_into(&mut self.buf)?;
+        if let Event::Start(start) = &event {
+            check_reply(&mut self.checked, &mut self.reader, start)?;
+        }
+        Ok(event)
+    }
+
+    /// Writes the text output of the reply to `out` as it arrives.
+    ///
+    /// This is the content of `<output>` for text replies and the bare text
+    /// of the reply for JSON. Surrounding whitespace is left out, as in the
+    /// buffered reply. Warnings are returned once the reply is complete, any
+    /// other `rpc-error` aborts the stream.
+    pub fn copy_output<W: Write>(&mut self, out: &mut W) -> NETCONFResult<RpcOutcome<()>> {
+        let mut warnings = vec![];
+        let mut path: Vec<Vec<u8>> = vec![];
+        let mut out = Trimmed::new(out);
+
+        loop {
+            let in_output = path.last().map(Vec::as_slice) == Some(b"output");
+            if in_output || (self.bare_output && path.len() == 1) {
+                self.copy_text(&mut out)?;
+            }
+
+            self.buf.clear();
+            let event = self.reader.read_event_into(&mut self.buf)?;
+            match event {
+                Event::Start(start) if start.local_name().as_ref() == b"rpc-error" => {
+                    let start = start.into_owned();
+                    let error = self.read_error(start)?;
+                    if error.error_severity == "warning" {
+                        warnings.push(error);
+                    } else {
+                        return Err(error.into());
+                    }
+                }
+                Event::Start(start) => {
+                    check_reply(&mut self.checked, &mut self.reader, &start)?;
+                    path.push(start.local_name().as_ref().to_vec());
+                }
+                Event::End(_) => {
+                    path.pop();
+                }
+                Event::CData(data) => out.write_all(&data)?,
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        out.flush()?;
+        Ok(RpcOutcome { value: (), warnings })
+    }
+
+    /// Writes the text following the last event up to the next markup to
+    /// `out`, unescaped and in chunks of at most `TEXT_CHUNK` bytes.
+    ///
+    /// Unlike the `Event::Text` returned by `next_event`, this never holds a
+    /// whole text node in memory, so it should be used for large outputs.
+    pub fn copy_text<W: Write>(&mut self, out: &mut W) -> NETCONFResult<()> {
+        let mut chunk = Vec::with_capacity(TEXT_CHUNK);
+        let mut entity = vec![];
+        loop {
+            let input = self.reader.get_mut().fill_buf()?;
+            if input.is_empty() {
+                break;
+            }
+            let markup = input.iter().position(|&b| b == b'<');
+            let len = markup.unwrap_or(input.len());
+
+            chunk.clear();
+            unescape_into(&input[..len], &mut entity, &mut chunk)?;
+            self.reader.get_mut().consume(len);
+            out.write_all(&chunk)?;
+
+            if markup.is_some() {
+                break;
+            }
+        }
+        if !entity.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unterminated entity in reply").into());
+        }
+        Ok(())
+    }
+
+    /// Collects the content of `<data>` as XML, as returned by `get` and `get-config`.
+    pub fn read_data(&mut self) -> NETCONFResult<RpcOutcome<String>> {
+        self.collect(b"data")
+    }
+
+    /// Collects the content of the reply as XML, without any `rpc-error`.
+    pub fn read_content(&mut self) -> NETCONFResult<RpcOutcome<String>> {
+        self.collect(b"rpc-reply")
+    }
+
+    /// Collects the content of the first element named `container` as XML.
+    /// Writes the content of `<rpc-reply>` to `out` as XML while it is
+    /// received, for XML outputs too large to be collected by `read_content`.
+    pub fn copy_content<W: Write>(&mut self, out: &mut W) -> NETCONFResult<RpcOutcome<()>> {
+        self.copy_container(b"rpc-reply", out)
+    }
+
+    fn collect(&mut self, container: &[u8]) -> NETCONFResult<RpcOutcome<String>> {
+        let mut content = vec![];
+        let outcome = self.copy_container(container, &mut content)?;
+        let value = String::from_utf8(content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(RpcOutcome {
+            value: value.trim().to_string(),
+            warnings: outcome.warnings,
+        })
+    }
+
+    /// Writes the content of the first `container` element to `out` as XML.
+    /// Whitespace directly inside the container is left out.
+    fn copy_container<W: Write>(&mut self, container: &[u8], out: &mut W) -> NETCONFResult<RpcOutcome<()>> {
+        let mut warnings = vec![];
+        let mut writer = quick_xml::Writer::new(out);
+        let mut depth = 0;
+
+        loop {
+            self.buf.clear();
+            let event = self.reader.read_event_into(&mut self.buf)?;
+            match event {
+                Event::Start(start) if start.local_name().as_ref() == b"rpc-error" => {
+                    let start = start.into_owned();
+                    let error = self.read_error(start)?;
+                    if error.error_severity == "warning" {
+                        warnings.push(error);
+                    } else {
+                        return Err(error.into());
+                    }
+                }
+                Event::Start(start) => {
+                    check_reply(&mut self.checked, &mut self.reader, &start)?;
+                    if depth > 0 {
+                        depth += 1;
+                        writer.write_event(Event::Start(start))?;
+                    } else if start.local_name().as_ref() == container {
+                        depth = 1;
+                    }
+                }
+                Event::End(end) if depth > 0 => {
+                    depth -= 1;
+                    if depth > 0 {
+                        writer.write_event(Event::End(end))?;
+                    }
+                }
+                Event::Text(text) if depth == 1 && text.iter().all(u8::is_ascii_whitespace) => {}
+                Event::Eof => break,
+                event if depth > 0 => {
+                    writer.write_event(event)?;
+                }
+                _ => {}
+            }
+        }
+
+        writer.into_inner().flush()?;
+        Ok(RpcOutcome { value: (), warnings })
+    }
+
+    /// Reads the rest of the `rpc-error` started by `start`.
+    fn read_error(&mut self, start: BytesStart<'static>) -> NETCONFResult<RPCError> {
+        let mut xml = vec![];
+        let mut writer = quick_xml::Writer::new(&mut xml);
+        writer.write_event(Event::Start(start))?;
+        let mut depth = 1;
+        while depth > 0 {
+            self.buf.clear();
+            let event = self.reader.read_event_into(&mut self.buf)?;
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            writer.write_event(event)?;
+        }
+
+        let error: RPCError = from_str(&String::from_utf8_lossy(&xml))?;
+        self.client()
+            .check_unknown_elements(error.unknown_elements())?;
+        Ok(error)
+    }
+
+    fn client(&mut self) -> &mut NETCONFClient {
+        self.reader.get_mut().get_mut().client
+    }
+}
+
+impl Drop for ReplyStream<'_> {
+    fn drop(&mut self) {
+        // The reply is discarded unseen, but it still answers the request.
+        if !self.checked {
+            self.client().pending.pop_front();
+        }
+    }
+}
+
+/// Writer leaving out leading and trailing whitespace. Whitespace is held
+/// back until it is known not to be trailing.
+struct Trimmed<'w, W: Write> {
+    out: &'w mut W,
+    started: bool,
+    pending: Vec<u8>,
+}
+
+impl<'w, W: Write> Trimmed<'w, W> {
+    fn new(out: &'w mut W) -> Trimmed<'w, W> {
+        Trimmed {
+            out,
+            started: false,
+            pending: vec![],
+        }
+    }
+}
+
+impl<W: Write> Write for Trimmed<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut text = buf;
+        if !self.started {
+            let start = text.iter().position(|b| !b.is_ascii_whitespace());
+            text = &text[start.unwrap_or(text.len())..];
+            self.started = !text.is_empty();
+        }
+        match text.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(last) => {
+                self.out.write_all(&self.pending)?;
+                self.pending.clear();
+                self.out.write_all(&text[..=last])?;
+                self.pending.extend_from_slice(&text[last + 1..]);
+            }
+            None => self.pending.extend_from_slice(text),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Appends `input` to `out` with entity references resolved. An entity cut
+/// off at the end of `input` is kept in `entity` to be completed by the next call.
+fn unescape_into(input: &[u8], entity: &mut Vec<u8>, out: &mut Vec<u8>) -> NETCONFResult<()> {
+    for &byte in input {
+        if entity.is_empty() && byte != b'&' {
+            out.push(byte);
+            continue;
+        }
+        entity.push(byte);
+        if byte == b';' {
+            let reference = std::str::from_utf8(entity)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            out.extend_from_slice(unescape(reference).map_err(quick_xml::Error::from)?.as_bytes());
+            entity.clear();
+        } else if entity.len() > MAX_ENTITY {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid entity in reply").into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks the message-id of the reply when the start tag of `rpc-reply` is read
+/// for the first time.
+///
+/// Takes the fields of `ReplyStream` separately, as the event being handled
+/// still borrows its buffer.
+fn check_reply(
+    checked: &mut bool,
+    reader: &mut Reader<BufReader<ReplyReader>>,
+    start: &BytesStart,
+) -> NETCONFResult<()> {
+    if *checked || start.local_name().as_ref() != b"rpc-reply" {
+        return Ok(());
+    }
+    let message_id = match start.try_get_attribute("message-id")? {
+        Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+        None => None,
+    };
+    *checked = true;
+    reader.get_mut().get_mut().client.check_message_id(message_id.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use super::TEXT_CHUNK;
+    use crate::netconf::transcript::{Direction, ReplayTransport, TranscriptEntry};
+    use crate::netconf::NETCONFClient;
+
+    /// Sink remembering the largest single write
+    #[derive(Default)]
+    struct Sink {
+        data: Vec<u8>,
+        largest_write: usize,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.largest_write = self.largest_write.max(buf.len());
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn received(message: String) -> TranscriptEntry {
+        TranscriptEntry {
+            timestamp: 0.0,
+            direction: Direction::Received,
+            message,
+        }
+    }
+
+    #[test]
+    fn copy_output_writes_large_output_in_chunks() {
+        let line = "ge-0/0/0 &lt;up&gt; &amp; &#x41;ctive\n";
+        let output = line.repeat(20_000_000 / line.len());
+        let transport = ReplayTransport::new(vec![
+            received("<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities></hello>".into()),
+            received(format!("<rpc-reply message-id=\"1\"><output>{}</output></rpc-reply>", output)),
+        ]);
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+
+        let mut sink = Sink::default();
+        client
+            .stream_command("show log messages".into(), "text".into())
+            .unwrap()
+            .copy_output(&mut sink)
+            .unwrap();
+
+        assert!(sink.largest_write <= TEXT_CHUNK, "largest write {}", sink.largest_write);
+        let expected = "ge-0/0/0 <up> & Active\n".repeat(20_000_000 / line.len());
+        assert!(sink.data == expected.trim_end().as_bytes());
+    }
+
+    #[test]
+    fn copy_output_leaves_out_surrounding_whitespace() {
+        let hello = "<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities></hello>";
+        let transport = ReplayTransport::new(vec![
+            received(hello.into()),
+            received("\n<rpc-reply message-id=\"1\">\n<output>\nHostname: r1\n\nModel: mx204\n</output>\n</rpc-reply>\n".into()),
+            received("\n<rpc-reply message-id=\"2\">\n{\n\"a\" : [1]\n}\n</rpc-reply>\n".into()),
+        ]);
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+
+        let mut sink = Sink::default();
+        client
+            .stream_command("show version".into(), "text".into())
+            .unwrap()
+            .copy_output(&mut sink)
+            .unwrap();
+        assert_eq!(String::from_utf8(sink.data).unwrap(), "Hostname: r1\n\nModel: mx204");
+
+        let mut sink = Sink::default();
+        client
+            .stream_command("show version".into(), "json".into())
+            .unwrap()
+            .copy_output(&mut sink)
+            .unwrap();
+        assert_eq!(String::from_utf8(sink.data).unwrap(), "{\n\"a\" : [1]\n}");
+    }
+
+    #[test]
+    fn copy_content_writes_reply_xml() {
+        let transport = ReplayTransport::new(vec![
+            received("<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities></hello>".into()),
+            received("\n<rpc-reply message-id=\"1\">\n<route-information>\n<rt a=\"x &amp; y\">10.0.0.0/8</rt>\n</route-information>\n</rpc-reply>\n".into()),
+        ]);
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+
+        let mut sink = Sink::default();
+        client
+            .stream_command("show route".into(), "xml".into())
+            .unwrap()
+            .copy_content(&mut sink)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(sink.data).unwrap(),
+            "<route-information>\n<rt a=\"x &amp; y\">10.0.0.0/8</rt>\n</route-information>"
+        );
+    }
+}
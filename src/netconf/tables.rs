@@ -0,0 +1,188 @@
+use quick_xml::de::from_str;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::error::NETCONFResult;
+use super::xml::seconds;
+use super::{NETCONFClient, RpcOutcome};
+
+/// Reply to `get-interface-information`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InterfaceInformation {
+    #[serde(rename(deserialize = "physical-interface"), default)]
+    pub physical_interfaces: Vec<PhysicalInterface>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PhysicalInterface {
+    pub name: String,
+    #[serde(rename(deserialize = "admin-status"))]
+    pub admin_status: Option<String>,
+    #[serde(rename(deserialize = "oper-status"))]
+    pub oper_status: Option<String>,
+    pub description: Option<String>,
+    pub mtu: Option<String>,
+    pub speed: Option<String>,
+    #[serde(rename(deserialize = "logical-interface"), default)]
+    pub logical_interfaces: Vec<LogicalInterface>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogicalInterface {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename(deserialize = "address-family"), default)]
+    pub address_families: Vec<AddressFamily>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AddressFamily {
+    #[serde(rename(deserialize = "address-family-name"))]
+    pub name: String,
+    #[serde(rename(deserialize = "interface-address"), default)]
+    pub addresses: Vec<InterfaceAddress>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InterfaceAddress {
+    #[serde(rename(deserialize = "ifa-local"))]
+    pub local: Option<String>,
+}
+
+/// Reply to `get-bgp-summary-information`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BgpSummary {
+    #[serde(rename(deserialize = "group-count"))]
+    pub group_count: Option<u32>,
+    #[serde(rename(deserialize = "peer-count"))]
+    pub peer_count: Option<u32>,
+    #[serde(rename(deserialize = "down-peer-count"))]
+    pub down_peer_count: Option<u32>,
+    #[serde(rename(deserialize = "bgp-peer"), default)]
+    pub peers: Vec<BgpPeer>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BgpPeer {
+    #[serde(rename(deserialize = "peer-address"))]
+    pub peer_address: String,
+    #[serde(rename(deserialize = "peer-as"))]
+    pub peer_as: Option<String>,
+    #[serde(rename(deserialize = "peer-state"))]
+    pub peer_state: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename(deserialize = "flap-count"))]
+    pub flap_count: Option<u32>,
+    /// Seconds since the last state change
+    #[serde(rename(deserialize = "elapsed-time"), deserialize_with = "seconds", default)]
+    pub elapsed_time: Option<u64>,
+    #[serde(rename(deserialize = "bgp-rib"), default)]
+    pub ribs: Vec<BgpPeerRib>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BgpPeerRib {
+    pub name: String,
+    #[serde(rename(deserialize = "active-prefix-count"))]
+    pub active_prefix_count: Option<u64>,
+    #[serde(rename(deserialize = "received-prefix-count"))]
+    pub received_prefix_count: Option<u64>,
+    #[serde(rename(deserialize = "accepted-prefix-count"))]
+    pub accepted_prefix_count: Option<u64>,
+}
+
+/// Reply to `get-lldp-neighbors-information`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LldpNeighbors {
+    #[serde(rename(deserialize = "lldp-neighbor-information"), default)]
+    pub neighbors: Vec<LldpNeighbor>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LldpNeighbor {
+    /// Called `lldp-local-interface` by older releases
+    #[serde(rename(deserialize = "lldp-local-port-id"), alias = "lldp-local-interface")]
+    pub local_port: Option<String>,
+    #[serde(rename(deserialize = "lldp-local-parent-interface-name"))]
+    pub local_parent_interface: Option<String>,
+    #[serde(rename(deserialize = "lldp-remote-chassis-id"))]
+    pub remote_chassis_id: Option<String>,
+    #[serde(rename(deserialize = "lldp-remote-port-id"))]
+    pub remote_port: Option<String>,
+    #[serde(rename(deserialize = "lldp-remote-port-description"))]
+    pub remote_port_description: Option<String>,
+    #[serde(rename(deserialize = "lldp-remote-system-name"))]
+    pub remote_system_name: Option<String>,
+}
+
+/// Reply to `get-route-summary-information`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RouteSummary {
+    #[serde(rename(deserialize = "as-number"))]
+    pub as_number: Option<String>,
+    #[serde(rename(deserialize = "router-id"))]
+    pub router_id: Option<String>,
+    #[serde(rename(deserialize = "route-table"), default)]
+    pub tables: Vec<RouteTableSummary>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RouteTableSummary {
+    #[serde(rename(deserialize = "table-name"))]
+    pub name: String,
+    #[serde(rename(deserialize = "destination-count"))]
+    pub destination_count: Option<u64>,
+    #[serde(rename(deserialize = "total-route-count"))]
+    pub total_route_count: Option<u64>,
+    #[serde(rename(deserialize = "active-route-count"))]
+    pub active_route_count: Option<u64>,
+    #[serde(rename(deserialize = "holddown-route-count"))]
+    pub holddown_route_count: Option<u64>,
+    #[serde(rename(deserialize = "hidden-route-count"))]
+    pub hidden_route_count: Option<u64>,
+    #[serde(rename(deserialize = "protocols"), default)]
+    pub protocols: Vec<ProtocolRouteCount>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProtocolRouteCount {
+    #[serde(rename(deserialize = "protocol-name"))]
+    pub protocol: String,
+    #[serde(rename(deserialize = "protocol-route-count"))]
+    pub route_count: Option<u64>,
+    #[serde(rename(deserialize = "active-route-count"))]
+    pub active_route_count: Option<u64>,
+}
+
+impl NETCONFClient {
+    /// Calls a Junos RPC and deserializes its XML reply.
+    fn call_typed<T: DeserializeOwned>(
+        &mut self,
+        name: &str,
+        args: &[(String, String)],
+    ) -> NETCONFResult<RpcOutcome<T>> {
+        let outcome = self.call(name, args, &[], "xml".to_string())?;
+        Ok(RpcOutcome {
+            value: from_str(&outcome.value)?,
+            warnings: outcome.warnings,
+        })
+    }
+
+    /// Physical and logical interfaces, optionally only those matching `name`.
+    pub fn interfaces(&mut self, name: Option<String>) -> NETCONFResult<RpcOutcome<InterfaceInformation>> {
+        let args: Vec<_> = name.into_iter().map(|name| ("interface-name".to_string(), name)).collect();
+        self.call_typed("get-interface-information", &args)
+    }
+
+    pub fn bgp_summary(&mut self) -> NETCONFResult<RpcOutcome<BgpSummary>> {
+        self.call_typed("get-bgp-summary-information", &[])
+    }
+
+    pub fn lldp_neighbors(&mut self) -> NETCONFResult<RpcOutcome<LldpNeighbors>> {
+        self.call_typed("get-lldp-neighbors-information", &[])
+    }
+
+    pub fn route_summary(&mut self) -> NETCONFResult<RpcOutcome<RouteSummary>> {
+        self.call_typed("get-route-summary-information", &[])
+    }
+}
@@ -0,0 +1,304 @@
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::framer::{Framer, DELIMITER};
+use super::redact::redact;
+use super::transport::Transport;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Single framed message of a recorded session, stored as one line of JSON.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TranscriptEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    pub direction: Direction,
+    /// Message without the `]]>]]>` delimiter
+    pub message: String,
+}
+
+/// Reads a transcript written by `RecordingTransport`.
+pub fn read_transcript<R: BufRead>(reader: R) -> io::Result<Vec<TranscriptEntry>> {
+    let mut entries = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Transport writing every message passing through it to a transcript.
+///
+/// Secrets are redacted from the recorded messages unless disabled with
+/// `set_redact`.
+pub struct RecordingTransport<T> {
+    inner: T,
+    output: Box<dyn Write>,
+    redact: bool,
+    sent: Framer,
+    received: Framer,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new<W: Write + 'static>(inner: T, output: W) -> RecordingTransport<T> {
+        RecordingTransport {
+            inner,
+            output: Box::new(output),
+            redact: true,
+            sent: Framer::new(),
+            received: Framer::new(),
+        }
+    }
+
+    /// Records messages as exchanged, including their secrets.
+    pub fn set_redact(&mut self, redact: bool) {
+        self.redact = redact;
+    }
+
+    /// Writes all complete messages buffered in `framer` to the transcript.
+    fn record(&mut self, direction: Direction) -> io::Result<()> {
+        let framer = match direction {
+            Direction::Sent => &mut self.sent,
+            Direction::Received => &mut self.received,
+        };
+        while let Some(message) = framer.next_message() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let message = String::from_utf8_lossy(&message);
+            let message = if self.redact { redact(&message).into_owned() } else { message.into_owned() };
+            let entry = TranscriptEntry {
+                timestamp,
+                direction,
+                message,
+            };
+            serde_json::to_writer(&mut *self.output, &entry)?;
+            self.output.write_all(b"\n")?;
+            self.output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Read for RecordingTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.received.push(&buf[..bytes]);
+        self.record(Direction::Received)?;
+        Ok(bytes)
+    }
+}
+
+impl<T: Transport> Write for RecordingTransport<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = self.inner.write(buf)?;
+        self.sent.push(&buf[..bytes]);
+        self.record(Direction::Sent)?;
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+
+    fn keepalive_interval(&self) -> Option<Duration> {
+        self.inner.keepalive_interval()
+    }
+
+    fn keepalive(&mut self) -> io::Result<()> {
+        self.inner.keepalive()
+    }
+}
+
+/// Transport playing back the received messages of a transcript, so a
+/// recorded session can be reproduced without the device.
+///
+/// Everything written to it is discarded. Once the transcript is exhausted,
+/// reads report the end of the session.
+pub struct ReplayTransport {
+    received: Cursor<Vec<u8>>,
+}
+
+impl ReplayTransport {
+    pub fn new<I: IntoIterator<Item = TranscriptEntry>>(entries: I) -> ReplayTransport {
+        let mut received = vec![];
+        for entry in entries {
+            if entry.direction == Direction::Received {
+                received.extend_from_slice(entry.message.as_bytes());
+                received.extend_from_slice(DELIMITER);
+            }
+        }
+        ReplayTransport {
+            received: Cursor::new(received),
+        }
+    }
+
+    /// Loads a transcript written by `RecordingTransport`.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<ReplayTransport> {
+        Ok(ReplayTransport::new(read_transcript(reader)?))
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.received.read(buf)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use super::{read_transcript, Direction, RecordingTransport, ReplayTransport, TranscriptEntry};
+    use crate::netconf::xml::Filter;
+    use crate::netconf::NETCONFClient;
+
+    const HELLO: &str = "<hello><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability></capabilities><session-id>4242</session-id></hello>";
+
+    fn received(message: &str) -> TranscriptEntry {
+        TranscriptEntry {
+            timestamp: 0.0,
+            direction: Direction::Received,
+            message: message.to_string(),
+        }
+    }
+
+    fn session() -> Vec<TranscriptEntry> {
+        vec![
+            received(HELLO),
+            received("<rpc-reply message-id=\"1\"><output>\nHostname: r1\n</output></rpc-reply>"),
+            received("<rpc-reply message-id=\"2\"><configuration-text>system { root-authentication { encrypted-password \"$6$salt$hash\"; } }</configuration-text></rpc-reply>"),
+            received("<rpc-reply message-id=\"3\"><ok/></rpc-reply>"),
+        ]
+    }
+
+    /// Transcript output which can still be read after the transport is dropped
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replays_session() {
+        let mut client = NETCONFClient::new(ReplayTransport::new(session()));
+        client.init().unwrap();
+        assert_eq!(client.session_id(), Some("4242"));
+
+        let outcome = client.send_command("show version".into(), "text".into()).unwrap();
+        assert_eq!(outcome.value.trim(), "Hostname: r1");
+        assert!(outcome.warnings.is_empty());
+
+        client.get_configuration("text".into()).unwrap();
+        client.close().unwrap();
+    }
+
+    fn record(redact: bool) -> Vec<TranscriptEntry> {
+        let output = Shared::default();
+        let mut transport = RecordingTransport::new(ReplayTransport::new(session()), output.clone());
+        transport.set_redact(redact);
+
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+        client.send_command("show version".into(), "text".into()).unwrap();
+        client.get_configuration("text".into()).unwrap();
+        client.close().unwrap();
+        drop(client);
+
+        let output = output.0.borrow();
+        read_transcript(output.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn records_redacted_session() {
+        let entries = record(true);
+        let sent = entries.iter().filter(|e| e.direction == Direction::Sent).count();
+        let received: Vec<_> = entries.iter().filter(|e| e.direction == Direction::Received).collect();
+        assert_eq!(sent, 4);
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0].message, HELLO);
+        assert!(received[2].message.contains("encrypted-password REDACTED;"));
+
+        // The redacted transcript still plays back.
+        let mut client = NETCONFClient::new(ReplayTransport::new(entries));
+        client.init().unwrap();
+        let outcome = client.send_command("show version".into(), "text".into()).unwrap();
+        assert_eq!(outcome.value.trim(), "Hostname: r1");
+        let configuration = client.get_configuration("text".into()).unwrap();
+        assert!(configuration.contains("encrypted-password REDACTED;"));
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn records_unredacted_session() {
+        let entries = record(false);
+        assert!(entries.iter().any(|e| e.message.contains("$6$salt$hash")));
+    }
+
+    #[test]
+    fn sends_xpath_filter_with_quotes() {
+        let output = Shared::default();
+        let replies = vec![received(HELLO), received("<rpc-reply message-id=\"1\"><data/></rpc-reply>")];
+        let transport = RecordingTransport::new(ReplayTransport::new(replies), output.clone());
+
+        let mut client = NETCONFClient::new(transport);
+        client.init().unwrap();
+        client
+            .get(Some(Filter::xpath("/interfaces/interface[name=\"ge-0/0/0\"]".into())))
+            .unwrap();
+        drop(client);
+
+        let output = output.0.borrow();
+        let entries = read_transcript(output.as_slice()).unwrap();
+        let rpc = entries.iter().find(|e| e.message.contains("<get>")).unwrap();
+        assert!(rpc.message.contains("select=\"/interfaces/interface[name=&quot;ge-0/0/0&quot;]\""));
+    }
+}
@@ -0,0 +1,147 @@
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Byte stream a NETCONF session runs over.
+pub trait Transport: Read + Write {
+    /// Limits how long a single read may block, `None` blocks indefinitely.
+    /// A read running into the timeout fails with `io::ErrorKind::TimedOut`.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Limits how long a single write may block, `None` blocks indefinitely.
+    /// A write running into the timeout fails with `io::ErrorKind::TimedOut`.
+    fn set_write_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Interval in which `keepalive` should be called while waiting for data.
+    fn keepalive_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Sends a keepalive message to the peer.
+    fn keepalive(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_write_timeout(timeout)
+    }
+
+    fn keepalive_interval(&self) -> Option<Duration> {
+        (**self).keepalive_interval()
+    }
+
+    fn keepalive(&mut self) -> io::Result<()> {
+        (**self).keepalive()
+    }
+}
+
+/// Reader enforcing the idle timeout and reply deadline of a NETCONF session
+/// and sending keepalives while waiting for data.
+pub(super) struct TimedReader<'a> {
+    pub transport: &'a mut dyn Transport,
+    pub deadline: Option<Instant>,
+    pub idle_timeout: Option<Duration>,
+}
+
+impl TimedReader<'_> {
+    fn read_timed(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let idle_deadline = self.idle_timeout.map(|timeout| Instant::now() + timeout);
+        let deadline = match (self.deadline, idle_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let keepalive_interval = self.transport.keepalive_interval();
+
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "timed out waiting for reply",
+                        ))
+                    }
+                },
+                None => None,
+            };
+            let wait = match (remaining, keepalive_interval) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            self.transport.set_read_timeout(wait)?;
+            match self.transport.read(buf) {
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => self.transport.keepalive()?,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Read for TimedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.read_timed(buf);
+        self.transport.set_read_timeout(None)?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::time::Duration;
+
+    use super::Transport;
+    use crate::netconf::{NETCONFClient, NETCONFError};
+
+    /// Transport of a peer which stopped reading, so writes only end by timing out.
+    #[derive(Default)]
+    struct StalledTransport {
+        write_timeout: Option<Duration>,
+    }
+
+    impl Read for StalledTransport {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl Write for StalledTransport {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            match self.write_timeout {
+                Some(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out")),
+                None => panic!("write without timeout would block forever"),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for StalledTransport {
+        fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+            self.write_timeout = timeout;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_times_out() {
+        let mut client = NETCONFClient::new(StalledTransport::default());
+        client.set_rpc_timeout(Some(Duration::from_secs(1)));
+        assert!(matches!(client.init(), Err(NETCONFError::Timeout(_))));
+    }
+}
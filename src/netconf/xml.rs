@@ -1,10 +1,12 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
+use serde::de::value::StrDeserializer;
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename = "hello")]
-#[serde(deny_unknown_fields)]
 pub struct Hello {
     pub capabilities: Capabilities,
     #[serde(rename = "session-id")]
@@ -13,10 +15,11 @@ pub struct Hello {
     #[serde(rename = "@xmlns")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: Extra,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct Capabilities {
     pub capability: Vec<String>,
 }
@@ -25,10 +28,24 @@ pub struct Capabilities {
 #[serde(rename = "rpc")]
 #[serde(deny_unknown_fields)]
 pub struct RPC {
+    /// Set by the client when the RPC is sent
+    #[serde(rename = "@message-id")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+
     #[serde(rename = "$value")]
     pub rpc: RPCCommand,
 }
 
+impl RPC {
+    pub fn new(rpc: RPCCommand) -> RPC {
+        RPC {
+            message_id: None,
+            rpc,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub enum RPCCommand {
@@ -37,11 +54,21 @@ pub enum RPCCommand {
         #[serde(rename = "@format")]
         format: String,
 
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "@database")]
+        database: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(rename = "@rollback")]
         rollback: Option<String>,
 
+        #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(rename = "@compare")]
         compare: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "@ephemeral-instance")]
+        ephemeral_instance: Option<String>,
     },
     #[serde(rename = "command")]
     Command {
@@ -77,6 +104,31 @@ pub enum RPCCommand {
     #[serde(rename = "commit-configuration")]
     CommitConfiguration {},
 
+    #[serde(rename = "open-configuration")]
+    OpenConfiguration {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        private: Option<Flag>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exclusive: Option<Flag>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "ephemeral-instance")]
+        ephemeral_instance: Option<String>,
+    },
+
+    #[serde(rename = "close-configuration")]
+    CloseConfiguration {},
+
+    #[serde(rename = "close-session")]
+    CloseSession {},
+
+    #[serde(rename = "kill-session")]
+    KillSession {
+        #[serde(rename = "session-id")]
+        session_id: String,
+    },
+
     #[serde(rename = "commit-configuration")]
     CommitConfirmedConfiguration {
         confirmed: ConfigurationConfirmed,
@@ -84,20 +136,243 @@ pub enum RPCCommand {
         #[serde(rename = "confirm-timeout")]
         confirm_timeout: i32,
     },
+
+    #[serde(rename = "get")]
+    Get {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        filter: Option<Filter>,
+    },
+
+    #[serde(rename = "get-config")]
+    GetConfig {
+        source: DatastoreRef,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        filter: Option<Filter>,
+    },
+
+    #[serde(rename = "edit-config")]
+    EditConfig {
+        target: DatastoreRef,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "default-operation")]
+        default_operation: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "test-option")]
+        test_option: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "error-option")]
+        error_option: Option<String>,
+
+        config: RawXml,
+    },
+
+    #[serde(rename = "copy-config")]
+    CopyConfig {
+        target: DatastoreRef,
+        source: DatastoreRef,
+    },
+
+    #[serde(rename = "delete-config")]
+    DeleteConfig { target: DatastoreRef },
+
+    #[serde(rename = "lock")]
+    Lock { target: DatastoreRef },
+
+    #[serde(rename = "unlock")]
+    Unlock { target: DatastoreRef },
+
+    #[serde(rename = "validate")]
+    Validate { source: DatastoreRef },
+
+    #[serde(rename = "commit")]
+    Commit {},
+
+    #[serde(rename = "discard-changes")]
+    DiscardChanges {},
+
+    /// RPC given as XML, for RPCs without a variant of their own
+    #[serde(rename = "$text")]
+    Raw(RawXml),
+}
+
+impl RPCCommand {
+    /// Name of the RPC element.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RPCCommand::GetConfiguration { .. } => "get-configuration",
+            RPCCommand::Command { .. } => "command",
+            RPCCommand::LockConfiguration {} => "lock-configuration",
+            RPCCommand::UnlockConfiguration {} => "unlock-configuration",
+            RPCCommand::LoadConfiguration { .. } => "load-configuration",
+            RPCCommand::CommitConfiguration {} => "commit-configuration",
+            RPCCommand::OpenConfiguration { .. } => "open-configuration",
+            RPCCommand::CloseConfiguration {} => "close-configuration",
+            RPCCommand::CloseSession {} => "close-session",
+            RPCCommand::KillSession { .. } => "kill-session",
+            RPCCommand::CommitConfirmedConfiguration { .. } => "commit-configuration",
+            RPCCommand::Get { .. } => "get",
+            RPCCommand::GetConfig { .. } => "get-config",
+            RPCCommand::EditConfig { .. } => "edit-config",
+            RPCCommand::CopyConfig { .. } => "copy-config",
+            RPCCommand::DeleteConfig { .. } => "delete-config",
+            RPCCommand::Lock { .. } => "lock",
+            RPCCommand::Unlock { .. } => "unlock",
+            RPCCommand::Validate { .. } => "validate",
+            RPCCommand::Commit {} => "commit",
+            RPCCommand::DiscardChanges {} => "discard-changes",
+            RPCCommand::Raw(_) => "raw",
+        }
+    }
+}
+
+/// Configuration datastore of RFC 6241, or a URL where the `:url` capability
+/// is supported.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Datastore {
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "candidate")]
+    Candidate,
+    #[serde(rename = "startup")]
+    Startup,
+    #[serde(rename = "url")]
+    Url(String),
+}
+
+/// Junos configuration database opened by `open-configuration`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigurationDatabase {
+    /// Private copy of the candidate, as with `configure private`
+    Private,
+    /// Candidate which only this session may change, as with `configure exclusive`
+    Exclusive,
+}
+
+/// Element like `<target>` or `<source>` naming a datastore.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatastoreRef {
+    #[serde(rename = "$value")]
+    pub datastore: Datastore,
+}
+
+impl From<Datastore> for DatastoreRef {
+    fn from(datastore: Datastore) -> Self {
+        DatastoreRef { datastore }
+    }
+}
+
+/// Filter of `get` and `get-config`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Filter {
+    /// `subtree` or `xpath`
+    #[serde(rename = "@type")]
+    pub filter_type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "@select")]
+    pub select: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "$text")]
+    pub subtree: Option<RawXml>,
+}
+
+impl Filter {
+    /// Selects the parts of the data matching the given XML subtree.
+    pub fn subtree(xml: String) -> Filter {
+        Filter {
+            filter_type: "subtree".to_string(),
+            select: None,
+            subtree: Some(RawXml(xml)),
+        }
+    }
+
+    /// Selects the nodes matching the given XPath expression, requires the `:xpath` capability.
+    pub fn xpath(select: String) -> Filter {
+        Filter {
+            filter_type: "xpath".to_string(),
+            select: Some(select),
+            subtree: None,
+        }
+    }
+}
+
+/// Duration given in the `junos:seconds` attribute of an element.
+pub(crate) fn seconds<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    struct Seconds {
+        #[serde(rename = "@junos:seconds", alias = "@seconds")]
+        seconds: Option<u64>,
+    }
+    Ok(Option::<Seconds>::deserialize(deserializer)?.and_then(|s| s.seconds))
+}
+
+const RAW_START: char = '\u{E000}';
+const RAW_END: char = '\u{E001}';
+
+/// XML embedded into a request as is.
+///
+/// The serializer can only write text, so the content is marked with
+/// private use characters and unescaped again by `unescape_raw`.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct RawXml(pub String);
+
+impl Serialize for RawXml {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}{}{}", RAW_START, self.0, RAW_END))
+    }
+}
+
+/// Restores the `RawXml` contents of a serialized request.
+pub fn unescape_raw(xml: &str) -> Result<String, quick_xml::escape::EscapeError> {
+    let mut result = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find(RAW_START) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + RAW_START.len_utf8()..];
+        let end = rest.find(RAW_END).unwrap_or(rest.len());
+        result.push_str(&quick_xml::escape::unescape(&rest[..end])?);
+        rest = rest[end..].strip_prefix(RAW_END).unwrap_or("");
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Whether `name` can be used as XML element name as is, e.g. for RPCs built
+/// from user input. Namespace prefixes are not accepted.
+pub fn is_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        }
+        _ => false,
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ConfigurationConfirmed {}
 
+/// Empty element like `<private/>`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Flag {}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename = "rpc-reply")]
 pub struct RPCReply {
-    #[serde(rename = "$value", default)]
+    #[serde(rename = "@message-id")]
+    pub message_id: Option<String>,
+
+    #[serde(rename = "$value", default, deserialize_with = "elements")]
     pub rpc_reply: Vec<RPCReplyCommand>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub enum RPCReplyCommand {
     #[serde(rename = "output")]
     Output {
@@ -114,6 +389,12 @@ pub enum RPCReplyCommand {
         configuration_output: String,
     },
 
+    #[serde(rename = "configuration-text")]
+    ConfigurationText(ConfigurationText),
+
+    #[serde(rename = "configuration-set")]
+    ConfigurationSet(ConfigurationText),
+
     #[serde(rename = "rpc-error")]
     RPCError(RPCError),
 
@@ -122,17 +403,29 @@ pub enum RPCReplyCommand {
 
     #[serde(rename = "$text")]
     Other(String),
+
+    /// Any element not known to rucli
+    #[serde(skip_deserializing)]
+    Unknown { name: String, content: XmlValue },
+}
+
+/// Configuration as returned by `get-configuration` in `text` or `set` format.
+///
+/// Junos annotates these elements with attributes like `junos:changed-seconds`,
+/// so unknown fields are accepted here.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConfigurationText {
+    #[serde(rename = "$text", default)]
+    pub text: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct LoadConfigurationResults {
-    #[serde(rename = "$value")]
+    #[serde(rename = "$value", deserialize_with = "elements")]
     pub load_configuration_results: Vec<LoadConfigurationResultsEnum>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub enum LoadConfigurationResultsEnum {
     #[serde(rename = "ok")]
     Ok,
@@ -142,13 +435,16 @@ pub enum LoadConfigurationResultsEnum {
 
     #[serde(rename = "load-error-count")]
     LoadErrorCount(LoadErrorCount),
+
+    /// Any element not known to rucli
+    #[serde(skip_deserializing)]
+    Unknown { name: String, content: XmlValue },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct LoadErrorCount {
     #[serde(rename = "$text")]
-    message: String,
+    pub message: String,
 }
 
 impl Display for RPCReply {
@@ -175,12 +471,19 @@ impl Display for RPCReplyCommand {
             } => {
                 write!(f, "{}", configuration_information)
             }
+            RPCReplyCommand::ConfigurationText(configuration)
+            | RPCReplyCommand::ConfigurationSet(configuration) => {
+                write!(f, "{}", configuration.text)
+            }
             RPCReplyCommand::Ok => {
                 write!(f, "Executed Successfully!")
             }
             RPCReplyCommand::RPCError(err) => {
                 write!(f, "{}", err)
             }
+            RPCReplyCommand::Unknown { name, .. } => {
+                write!(f, "(unknown element {})", name)
+            }
             RPCReplyCommand::LoadConfigurationResults(x) => {
                 for elem in &x.load_configuration_results {
                     match elem {
@@ -193,6 +496,9 @@ impl Display for RPCReplyCommand {
                         LoadConfigurationResultsEnum::LoadErrorCount(l)=> {
                             writeln!(f, "{:?}", l)?;
                         }
+                        LoadConfigurationResultsEnum::Unknown { name, .. } => {
+                            writeln!(f, "(unknown element {})", name)?;
+                        }
                     }
                 }
 
@@ -203,12 +509,12 @@ impl Display for RPCReplyCommand {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct RPCError {
     #[serde(rename = "error-severity")]
     pub error_severity: String,
+    /// Optional as of RFC 6241, the tag describes the error then
     #[serde(rename = "error-message")]
-    pub error_message: String,
+    pub error_message: Option<String>,
 
     #[serde(rename = "error-path")]
     pub error_path: Option<String>,
@@ -220,13 +526,57 @@ pub struct RPCError {
     pub error_info: Option<RPCErrorInfo>,
     #[serde(rename = "source-daemon")]
     pub source_daemon: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct RPCErrorInfo {
-    #[serde(rename = "bad-element")]
-    pub bad_element: String,
+    /// Elements the error refers to, may be repeated
+    #[serde(rename = "bad-element", default)]
+    pub bad_element: Vec<String>,
+    /// Session holding a lock, for `lock-denied` errors
+    #[serde(rename = "session-id")]
+    pub session_id: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl RPCError {
+    /// Whether the error reports a lock held by another session.
+    ///
+    /// Junos also denies locking a candidate with uncommitted changes, which
+    /// waiting for doesn't help and so isn't reported here.
+    pub fn is_lock_denied(&self) -> bool {
+        let message = self.error_message.as_deref().unwrap_or_default();
+        if message.contains("configuration database modified") {
+            return false;
+        }
+        self.error_tag.as_deref() == Some("lock-denied")
+            || self
+                .error_message
+                .as_deref()
+                .is_some_and(|message| message.trim_start().starts_with("configuration database locked by"))
+    }
+
+    /// Describes who holds the lock of a `lock-denied` error, e.g.
+    /// `jdoe terminal pts/0 (pid 4711) on since 2024-05-02 10:00:00 UTC (session-id 4711)`.
+    pub fn lock_holder(&self) -> String {
+        // Junos names the holder in the line following "locked by:"
+        let user = self
+            .error_message
+            .as_deref()
+            .and_then(|message| message.split_once("locked by"))
+            .and_then(|(_, rest)| rest.trim_start_matches(':').lines().map(str::trim).find(|l| !l.is_empty()))
+            .map(str::to_owned);
+        let session_id = self.error_info.as_ref().and_then(|info| info.session_id.as_deref());
+        match (user, session_id) {
+            (Some(user), Some(session_id)) => format!("{} (session-id {})", user, session_id.trim()),
+            (Some(user), None) => user,
+            (None, Some(session_id)) => format!("session-id {}", session_id.trim()),
+            (None, None) => "another session".to_string(),
+        }
+    }
 }
 
 impl Display for RPCError {
@@ -243,12 +593,15 @@ impl Display for RPCError {
                 error_path
             )?;
         }
-        write!(f, ": {}", self.error_message)?;
-        if let Some(error_info) = &self.error_info {
+        if let Some(message) = self.error_message.as_ref().or(self.error_tag.as_ref()) {
+            write!(f, ": {}", message)?;
+        }
+        let bad_elements = self.error_info.as_ref().map(|i| i.bad_element.as_slice()).unwrap_or_default();
+        if !bad_elements.is_empty() {
             write!(
                 f,
                 " (bad element: {})",
-                error_info.bad_element
+                bad_elements.join(", ")
             )?;
         }
         write!(f, "")?;
@@ -257,3 +610,382 @@ impl Display for RPCError {
 }
 
 impl std::error::Error for RPCError { }
+
+/// Content of an XML element not modeled by rucli.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum XmlValue {
+    /// Text content of an element without attributes or children, or an attribute value
+    Text(String),
+    /// Attributes (prefixed with `@`), children and text (`$text`) of an element
+    Element(BTreeMap<String, XmlValue>),
+    /// Repeated elements of the same name
+    List(Vec<XmlValue>),
+}
+
+impl<'de> Deserialize<'de> for XmlValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct XmlValueVisitor;
+
+        impl<'de> Visitor<'de> for XmlValueVisitor {
+            type Value = XmlValue;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("any XML content")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<XmlValue, E> {
+                Ok(XmlValue::Text(v.to_owned()))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<XmlValue, E> {
+                Ok(XmlValue::Text(v))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<XmlValue, E> {
+                Ok(XmlValue::Text(String::new()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<XmlValue, A::Error> {
+                let mut elements = Extra::visit(map)?.0;
+                if elements.len() == 1 {
+                    if let Some(text) = elements.remove("$text") {
+                        return Ok(text);
+                    }
+                }
+                Ok(XmlValue::Element(elements))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<XmlValue, A::Error> {
+                let mut values = vec![];
+                while let Some(value) = seq.next_element()? {
+                    values.push(value);
+                }
+                Ok(XmlValue::List(values))
+            }
+        }
+
+        deserializer.deserialize_any(XmlValueVisitor)
+    }
+}
+
+/// Elements and attributes of a reply not modeled by rucli, preserved so
+/// newer Junos releases adding elements don't break parsing.
+#[derive(Debug, Default, Serialize)]
+pub struct Extra(pub BTreeMap<String, XmlValue>);
+
+impl Extra {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Names of unknown elements and attributes, ignoring namespace declarations.
+    pub fn unknown_names(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .keys()
+            .map(String::as_str)
+            .filter(|name| *name != "@xmlns" && !name.starts_with("@xmlns:"))
+    }
+
+    fn visit<'de, A: MapAccess<'de>>(mut map: A) -> Result<Extra, A::Error> {
+        let mut elements: BTreeMap<String, XmlValue> = BTreeMap::new();
+        while let Some((name, value)) = map.next_entry::<String, XmlValue>()? {
+            match elements.remove(&name) {
+                Some(XmlValue::List(mut values)) => {
+                    values.push(value);
+                    elements.insert(name, XmlValue::List(values));
+                }
+                Some(previous) => {
+                    elements.insert(name, XmlValue::List(vec![previous, value]));
+                }
+                None => {
+                    elements.insert(name, value);
+                }
+            }
+        }
+        Ok(Extra(elements))
+    }
+}
+
+impl<'de> Deserialize<'de> for Extra {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ExtraVisitor;
+
+        impl<'de> Visitor<'de> for ExtraVisitor {
+            type Value = Extra;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("XML elements")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Extra, A::Error> {
+                Extra::visit(map)
+            }
+        }
+
+        deserializer.deserialize_map(ExtraVisitor)
+    }
+}
+
+/// Element of a reply enum, which keeps elements not known to rucli.
+trait UnknownElement {
+    fn unknown(name: String, content: XmlValue) -> Self;
+}
+
+impl UnknownElement for RPCReplyCommand {
+    fn unknown(name: String, content: XmlValue) -> Self {
+        RPCReplyCommand::Unknown { name, content }
+    }
+}
+
+impl UnknownElement for LoadConfigurationResultsEnum {
+    fn unknown(name: String, content: XmlValue) -> Self {
+        LoadConfigurationResultsEnum::Unknown { name, content }
+    }
+}
+
+/// Deserializes a sequence of elements into variants of `T`, keeping elements
+/// which aren't a variant with their name and content like `Extra` does.
+fn elements<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + UnknownElement,
+{
+    struct ElementsVisitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de> + UnknownElement> Visitor<'de> for ElementsVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("XML elements")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+            let mut elements = vec![];
+            while let Some(element) = seq.next_element_seed(ElementVisitor(std::marker::PhantomData))? {
+                elements.push(element);
+            }
+            Ok(elements)
+        }
+    }
+
+    deserializer.deserialize_seq(ElementsVisitor(std::marker::PhantomData))
+}
+
+/// Deserializes a single element of `elements`.
+struct ElementVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: Deserialize<'de> + UnknownElement> DeserializeSeed<'de> for ElementVisitor<T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        deserializer.deserialize_enum("", &[], self)
+    }
+}
+
+impl<'de, T: Deserialize<'de> + UnknownElement> Visitor<'de> for ElementVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("XML element")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<T, A::Error> {
+        let (name, variant) = data.variant::<String>()?;
+        let mut variant = Some(variant);
+        let known = T::deserialize(KnownElement {
+            name: &name,
+            variant: &mut variant,
+        });
+        match (known, variant) {
+            (Ok(element), _) => Ok(element),
+            // The name was rejected before its content was read
+            (Err(_), Some(variant)) => Ok(T::unknown(name, variant.newtype_variant()?)),
+            (Err(e), None) => Err(e),
+        }
+    }
+}
+
+/// Element whose name was already read, offering it once more to the
+/// `Deserialize` implementation of an enum. The content is only taken if the
+/// name is a variant of the enum.
+struct KnownElement<'a, A> {
+    name: &'a str,
+    variant: &'a mut Option<A>,
+}
+
+impl<'de, A: VariantAccess<'de>> Deserializer<'de> for KnownElement<'_, A> {
+    type Error = A::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, A::Error> {
+        Err(de::Error::custom("expected an enum"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> EnumAccess<'de> for KnownElement<'_, A> {
+    type Error = A::Error;
+    type Variant = A;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, A), A::Error> {
+        let name: StrDeserializer<A::Error> = self.name.into_deserializer();
+        let value = seed.deserialize(name)?;
+        let variant = self.variant.take().ok_or_else(|| de::Error::custom("element read twice"))?;
+        Ok((value, variant))
+    }
+}
+
+impl Hello {
+    /// Names of elements in the hello not known to rucli.
+    pub fn unknown_elements(&self) -> Vec<String> {
+        self.extra.unknown_names().map(str::to_owned).collect()
+    }
+}
+
+impl RPCError {
+    /// Names of elements in the error not known to rucli.
+    pub fn unknown_elements(&self) -> Vec<String> {
+        let mut unknown: Vec<String> = self.extra.unknown_names().map(str::to_owned).collect();
+        if let Some(error_info) = &self.error_info {
+            unknown.extend(
+                error_info
+                    .extra
+                    .unknown_names()
+                    .map(|name| format!("error-info/{}", name)),
+            );
+        }
+        unknown
+    }
+}
+
+impl RPCReply {
+    /// Names of elements in the reply not known to rucli.
+    pub fn unknown_elements(&self) -> Vec<String> {
+        let mut unknown = vec![];
+        for reply in &self.rpc_reply {
+            match reply {
+                RPCReplyCommand::Unknown { name, .. } => unknown.push(name.clone()),
+                RPCReplyCommand::RPCError(error) => unknown.extend(error.unknown_elements()),
+                RPCReplyCommand::LoadConfigurationResults(results) => {
+                    for result in &results.load_configuration_results {
+                        match result {
+                            LoadConfigurationResultsEnum::Unknown { name, .. } => {
+                                unknown.push(format!("load-configuration-results/{}", name))
+                            }
+                            LoadConfigurationResultsEnum::RPCError(error) => {
+                                unknown.extend(error.unknown_elements())
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quick_xml::de::from_str;
+
+    use super::{is_element_name, LoadConfigurationResultsEnum, RPCReply, RPCReplyCommand, XmlValue};
+
+    #[test]
+    fn keeps_unknown_elements() {
+        let reply: RPCReply = from_str(
+            r#"<rpc-reply message-id="1"><commit-results><routing-engine><name>re0</name></routing-engine></commit-results><ok/></rpc-reply>"#,
+        )
+        .unwrap();
+        assert_eq!(reply.unknown_elements(), vec!["commit-results"]);
+        match &reply.rpc_reply[..] {
+            [RPCReplyCommand::Unknown { name, content: XmlValue::Element(content) }, RPCReplyCommand::Ok] => {
+                assert_eq!(name, "commit-results");
+                assert!(content.contains_key("routing-engine"));
+            }
+            other => panic!("unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_load_results() {
+        let reply: RPCReply = from_str(
+            r#"<rpc-reply message-id="1"><load-configuration-results><load-success/><ok/></load-configuration-results></rpc-reply>"#,
+        )
+        .unwrap();
+        assert_eq!(reply.unknown_elements(), vec!["load-configuration-results/load-success"]);
+        let RPCReplyCommand::LoadConfigurationResults(results) = &reply.rpc_reply[0] else {
+            panic!("unexpected reply {:?}", reply.rpc_reply);
+        };
+        assert!(matches!(
+            results.load_configuration_results[..],
+            [LoadConfigurationResultsEnum::Unknown { .. }, LoadConfigurationResultsEnum::Ok]
+        ));
+    }
+
+    #[test]
+    fn reads_repeated_bad_elements() {
+        let reply: RPCReply = from_str(
+            r#"<rpc-reply message-id="1"><rpc-error><error-severity>error</error-severity><error-message>syntax error</error-message><error-info><bad-element>foo</bad-element><bad-element>bar</bad-element></error-info></rpc-error></rpc-reply>"#,
+        )
+        .unwrap();
+        let RPCReplyCommand::RPCError(error) = &reply.rpc_reply[0] else {
+            panic!("unexpected reply {:?}", reply.rpc_reply);
+        };
+        assert_eq!(error.error_info.as_ref().unwrap().bad_element, vec!["foo", "bar"]);
+        assert_eq!(error.to_string(), "error: syntax error (bad element: foo, bar)");
+    }
+
+    #[test]
+    fn validates_element_names() {
+        assert!(is_element_name("get-interface-information"));
+        assert!(is_element_name("_x.1"));
+        assert!(!is_element_name(""));
+        assert!(!is_element_name("1st"));
+        assert!(!is_element_name("terse/><kill"));
+        assert!(!is_element_name("interface name"));
+        assert!(!is_element_name("junos:rpc"));
+    }
+
+    #[test]
+    fn reads_error_without_message() {
+        let reply: RPCReply = from_str(
+            r#"<rpc-reply message-id="1"><rpc-error><error-type>protocol</error-type><error-tag>lock-denied</error-tag><error-severity>error</error-severity><error-info><session-id>4711</session-id></error-info></rpc-error></rpc-reply>"#,
+        )
+        .unwrap();
+        let RPCReplyCommand::RPCError(error) = &reply.rpc_reply[0] else {
+            panic!("unexpected reply {:?}", reply.rpc_reply);
+        };
+        assert_eq!(error.error_message, None);
+        assert!(error.is_lock_denied());
+        assert_eq!(error.lock_holder(), "session-id 4711");
+        assert_eq!(error.to_string(), "error: lock-denied");
+    }
+
+    #[test]
+    fn modified_candidate_is_not_locked() {
+        let reply: RPCReply = from_str(
+            r#"<rpc-reply message-id="1"><rpc-error><error-type>protocol</error-type><error-tag>lock-denied</error-tag><error-severity>error</error-severity><error-message>configuration database modified</error-message></rpc-error></rpc-reply>"#,
+        )
+        .unwrap();
+        let RPCReplyCommand::RPCError(error) = &reply.rpc_reply[0] else {
+            panic!("unexpected reply {:?}", reply.rpc_reply);
+        };
+        assert!(!error.is_lock_denied());
+    }
+}
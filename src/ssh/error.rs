@@ -1,17 +1,7 @@
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum SSHError {
-    SSHError(ssh2::Error),
-    IoError(std::io::Error),
-}
-
-impl From<ssh2::Error> for SSHError {
-    fn from(err: ssh2::Error) -> Self {
-        SSHError::SSHError(err)
-    }
-}
-
-impl From<std::io::Error> for SSHError {
-    fn from(err: std::io::Error) -> Self {
-        SSHError::IoError(err)
-    }
+    #[error("{0}")]
+    SSHError(#[from] ssh2::Error),
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
 }
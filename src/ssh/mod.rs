@@ -1,8 +1,12 @@
-use std::net::TcpStream;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
 
 use ssh2::{Session, TraceFlags};
+use tracing::{debug, info};
 
 use self::error::SSHError;
+use crate::netconf::Transport;
 
 pub mod error;
 
@@ -12,6 +16,10 @@ pub struct SSHConnection {
     pub target: String,
     pub debug: bool,
 
+    pub connect_timeout: Option<Duration>,
+    pub handshake_timeout: Option<Duration>,
+    pub keepalive_interval: Option<Duration>,
+
     pub sess: Option<ssh2::Session>,
     pub channel: Option<ssh2::Channel>,
 }
@@ -23,31 +31,141 @@ impl SSHConnection {
             password: password,
             target: String::from(target),
             debug,
+            connect_timeout: None,
+            handshake_timeout: None,
+            keepalive_interval: None,
             sess: None,
             channel: None,
         };
     }
 
+    /// Sets how long to wait for the TCP connection to be established.
+    pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Sets how long the SSH handshake, authentication and opening the
+    /// NETCONF subsystem may take.
+    pub fn set_handshake_timeout(&mut self, timeout: Option<Duration>) {
+        self.handshake_timeout = timeout;
+    }
+
+    /// Sets the interval in which SSH keepalives are sent while waiting for replies.
+    pub fn set_keepalive_interval(&mut self, interval: Option<Duration>) {
+        self.keepalive_interval = interval;
+    }
+
+    fn connect_tcp(&self) -> Result<TcpStream, SSHError> {
+        let timeout = match self.connect_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(TcpStream::connect(self.target.as_str())?),
+        };
+
+        let mut last_error = None;
+        for addr in self.target.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(tcp) => return Ok(tcp),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error
+            .unwrap_or(io::Error::new(io::ErrorKind::NotFound, "could not resolve target"))
+            .into())
+    }
+
     pub fn connect(&mut self) -> Result<(), SSHError> {
-        let tcp = TcpStream::connect(self.target.as_str())?;
+        debug!(target = self.target, "connecting");
+        let tcp = self.connect_tcp()?;
         let mut sess = Session::new()?;
         sess.set_tcp_stream(tcp);
         if self.debug {
             sess.trace(TraceFlags::AUTH | TraceFlags::KEX | TraceFlags::PUBLICKEY);
         };
+        if let Some(timeout) = self.handshake_timeout {
+            sess.set_timeout(timeout_ms(Some(timeout)));
+        }
+        if let Some(interval) = self.keepalive_interval {
+            sess.set_keepalive(false, interval.as_secs().max(1) as u32);
+        }
         sess.handshake()?;
+        debug!(target = self.target, "SSH handshake completed");
         if self.password.is_some() {
+            debug!(user = self.user, method = "password", "authenticating");
             sess.userauth_password(self.user.as_str(), self.password.clone().unwrap().as_str())?;
         } else {
+            debug!(user = self.user, method = "agent", "authenticating");
             sess.userauth_agent(self.user.as_str())?;
         }
+        info!(target = self.target, user = self.user, "SSH session established");
 
         let mut channel = sess.channel_session()?;
         channel.subsystem("netconf")?;
+        sess.set_timeout(0);
 
         self.sess = Some(sess);
         self.channel = Some(channel);
 
         return Ok(());
     }
+
+    /// Takes the established session as transport for a `NETCONFClient`.
+    pub fn transport(&mut self) -> Option<SSHTransport> {
+        Some(SSHTransport {
+            sess: self.sess.clone()?,
+            channel: self.channel.take()?,
+            keepalive_interval: self.keepalive_interval,
+        })
+    }
+}
+
+/// NETCONF subsystem channel of an SSH session.
+pub struct SSHTransport {
+    sess: ssh2::Session,
+    channel: ssh2::Channel,
+    keepalive_interval: Option<Duration>,
+}
+
+impl Read for SSHTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.channel.read(buf)
+    }
+}
+
+impl Write for SSHTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.channel.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+}
+
+impl Transport for SSHTransport {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sess.set_timeout(timeout_ms(timeout));
+        Ok(())
+    }
+
+    /// Shares the timeout with reads, libssh2 has a single one per session.
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+
+    fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval
+    }
+
+    fn keepalive(&mut self) -> io::Result<()> {
+        self.sess.keepalive_send()?;
+        Ok(())
+    }
+}
+
+/// Converts a timeout for libssh2, where 0 means no timeout.
+fn timeout_ms(timeout: Option<Duration>) -> u32 {
+    match timeout {
+        Some(timeout) => timeout.as_millis().clamp(1, u32::MAX as u128) as u32,
+        None => 0,
+    }
 }
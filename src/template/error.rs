@@ -0,0 +1,11 @@
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+    #[error("{0}")]
+    TomlError(#[from] toml::de::Error),
+    #[error("{0}")]
+    RenderError(#[from] minijinja::Error),
+}
+
+pub type TemplateResult<T> = Result<T, TemplateError>;
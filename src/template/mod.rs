@@ -0,0 +1,75 @@
+use std::fs;
+
+use minijinja::{Environment, UndefinedBehavior, Value};
+use toml::Table;
+
+mod error;
+
+pub use self::error::{TemplateError, TemplateResult};
+
+/// Variables used to render a configuration template for a single host.
+///
+/// Variables are merged in the order they are loaded, later sources
+/// overriding earlier ones. The `hostname` variable is always set.
+pub struct TemplateContext {
+    hostname: String,
+    vars: Table,
+}
+
+impl TemplateContext {
+    pub fn new(hostname: &str) -> TemplateContext {
+        let mut vars = Table::new();
+        vars.insert("hostname".to_owned(), hostname.into());
+
+        TemplateContext {
+            hostname: hostname.to_owned(),
+            vars,
+        }
+    }
+
+    /// Loads variables from an inventory file.
+    ///
+    /// The inventory is a TOML file with defaults in a `[vars]` table and
+    /// per-host overrides in `[hosts.<hostname>]` tables.
+    pub fn load_inventory(&mut self, path: &str) -> TemplateResult<()> {
+        let mut inventory: Table = fs::read_to_string(path)?.parse()?;
+
+        if let Some(toml::Value::Table(vars)) = inventory.remove("vars") {
+            self.merge(vars);
+        }
+        if let Some(toml::Value::Table(mut hosts)) = inventory.remove("hosts") {
+            if let Some(toml::Value::Table(vars)) = hosts.remove(&self.hostname) {
+                self.merge(vars);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads variables from a TOML file.
+    pub fn load_vars(&mut self, path: &str) -> TemplateResult<()> {
+        let vars: Table = fs::read_to_string(path)?.parse()?;
+        self.merge(vars);
+
+        Ok(())
+    }
+
+    fn merge(&mut self, vars: Table) {
+        for (key, value) in vars {
+            self.vars.insert(key, value);
+        }
+    }
+
+    /// Renders the given template source. Undefined variables are an error.
+    pub fn render(&self, name: &str, source: &str) -> TemplateResult<String> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_keep_trailing_newline(true);
+        env.add_template(name, source)?;
+
+        let rendered = env
+            .get_template(name)?
+            .render(Value::from_serialize(&self.vars))?;
+        Ok(rendered)
+    }
+}